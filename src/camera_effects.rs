@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    console::{Console, ConsoleCommand},
+    fps_movement::{FPSMovement, GroundState},
+    landing::Landing,
+    noclip::Noclip,
+    player_health::{PlayerDamageEvent, PlayerHealth},
+    settings_file,
+    sprint::Sprint,
+    team::{FriendlyFire, Team},
+    traversal::Traversal,
+};
+
+pub const CAMERA_EFFECTS_PATH: &str = "config/camera_effects.ron";
+
+//trauma from an enemy shot that just missed the player
+pub const NEAR_MISS_TRAUMA: f32 = 0.2;
+pub const NEAR_MISS_RADIUS: f32 = 3.;
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct EffectLayer {
+    pub enabled: bool,
+    //1 is the intended strength
+    pub scale: f32,
+}
+
+impl Default for EffectLayer {
+    fn default() -> Self {
+        EffectLayer { enabled: true, scale: 1. }
+    }
+}
+
+impl EffectLayer {
+    pub fn amount(&self) -> f32 {
+        if self.enabled {
+            self.scale.max(0.)
+        } else {
+            0.
+        }
+    }
+}
+
+//player preferences for each layer, for anyone who gets motion sick they can be turned down or off
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraEffectSettings {
+    pub head_bob: EffectLayer,
+    pub fov_kick: EffectLayer,
+    pub screen_shake: EffectLayer,
+    pub aim_punch: EffectLayer,
+}
+
+impl CameraEffectSettings {
+    pub fn load_or_default(path: &str) -> Self {
+        settings_file::load(path).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &str) {
+        settings_file::save(self, path);
+    }
+
+    fn layer_mut(&mut self, name: &str) -> Option<&mut EffectLayer> {
+        match name {
+            "bob" => Some(&mut self.head_bob),
+            "fov" => Some(&mut self.fov_kick),
+            "shake" => Some(&mut self.screen_shake),
+            "punch" => Some(&mut self.aim_punch),
+            _ => None,
+        }
+    }
+}
+
+//adds trauma to the screen shake, fading out to nothing at radius, explosions and impacts send these
+pub struct CameraShake {
+    //none shakes the player wherever they are
+    pub position: Option<Vec3>,
+    pub trauma: f32,
+    pub radius: f32,
+}
+
+#[derive(Component)]
+pub struct CameraEffects {
+    //distance walked per step, one step is half a bob cycle
+    pub bob_stride: f32,
+    pub bob_height: f32,
+    pub bob_sway: f32,
+    //radians
+    pub bob_roll: f32,
+    //how quickly the bob fades in and out as the player starts and stops
+    pub bob_fade_speed: f32,
+    pub bob_phase: f32,
+    pub bob_weight: f32,
+    //radians the fov widens by at full sprint
+    pub sprint_fov_kick: f32,
+    pub fov_kick_speed: f32,
+    pub fov_kick: f32,
+    //0 to 1, the shake grows with its square so small hits stay small
+    pub trauma: f32,
+    //trauma lost per second
+    pub trauma_decay: f32,
+    //radians of pitch and yaw at full trauma, and of roll
+    pub max_shake_angle: f32,
+    pub max_shake_roll: f32,
+    pub shake_frequency: f32,
+    pub shake_time: f32,
+    //landing faster than this adds trauma, scaled per unit of speed above it
+    pub landing_trauma_speed: f32,
+    pub landing_trauma: f32,
+    //radians the view is knocked up per point of damage taken
+    pub punch_per_damage: f32,
+    pub max_punch: f32,
+    //how quickly the punch settles, per second
+    pub punch_recovery: f32,
+    //x is pitch and y is yaw
+    pub punch: Vec2,
+
+    //what move_camera adds on top of the aim, pitch, yaw and roll in radians
+    pub angles: Vec3,
+    //local to the camera
+    pub offset: Vec3,
+    //radians added to the fov
+    pub fov_offset: f32,
+}
+
+impl Default for CameraEffects {
+    fn default() -> Self {
+        CameraEffects {
+            bob_stride: 1.6,
+            bob_height: 0.03,
+            bob_sway: 0.02,
+            bob_roll: 0.005,
+            bob_fade_speed: 6.,
+            bob_phase: 0.,
+            bob_weight: 0.,
+            sprint_fov_kick: 0.14,
+            fov_kick_speed: 5.,
+            fov_kick: 0.,
+            trauma: 0.,
+            trauma_decay: 1.2,
+            max_shake_angle: 0.05,
+            max_shake_roll: 0.03,
+            shake_frequency: 18.,
+            shake_time: 0.,
+            landing_trauma_speed: 7.,
+            landing_trauma: 0.05,
+            punch_per_damage: 0.004,
+            max_punch: 0.12,
+            punch_recovery: 8.,
+            punch: Vec2::ZERO,
+            angles: Vec3::ZERO,
+            offset: Vec3::ZERO,
+            fov_offset: 0.,
+        }
+    }
+}
+
+//a few sines at odd ratios, smooth but without an obvious repeat, -1 to 1
+fn shake_noise(time: f32, seed: f32) -> f32 {
+    (time + seed).sin() * 0.5 + (time * 2.31 + seed * 1.7).sin() * 0.3 + (time * 4.13 + seed * 2.9).sin() * 0.2
+}
+
+//knocks the view when the player is hit, on the tick so no hit is missed between frames
+pub fn apply_aim_punch(
+    friendly_fire: Res<FriendlyFire>,
+    mut damage_events: EventReader<PlayerDamageEvent>,
+    mut player_query: Query<(&mut CameraEffects, &Team, &PlayerHealth)>,
+) {
+    let Ok((mut effects, player_team, player_health)) = player_query.get_single_mut() else {
+        damage_events.clear();
+        return;
+    };
+    for ev in damage_events.iter() {
+        //falls and hazards have their own feedback, only shots punch
+        let Some(team) = ev.team else {
+            continue;
+        };
+        let amount = ev.amount * friendly_fire.damage_scale(team, *player_team);
+        if amount <= 0. || player_health.dead {
+            continue;
+        }
+        let side = if rand::thread_rng().gen::<bool>() { 1. } else { -1. };
+        let punch = effects.punch + Vec2::new(1., side * 0.5) * amount * effects.punch_per_damage;
+        effects.punch = punch.clamp_length_max(effects.max_punch);
+    }
+}
+
+//works out every layer for this frame, move_camera and apply_fov put the result on the camera
+#[allow(clippy::type_complexity)]
+pub fn update_camera_effects(
+    time: Res<Time>,
+    settings: Res<CameraEffectSettings>,
+    mut shake_events: EventReader<CameraShake>,
+    mut landing_events: EventReader<Landing>,
+    mut player_query: Query<(
+        Entity,
+        &Transform,
+        &mut CameraEffects,
+        &Velocity,
+        &FPSMovement,
+        &GroundState,
+        &PlayerHealth,
+        Option<&Sprint>,
+        Option<&Traversal>,
+        Option<&Noclip>,
+    )>,
+) {
+    let delta = time.delta_seconds();
+    let Ok((entity, transform, mut effects, velocity, movement, ground, player_health, sprint, traversal, noclip)) =
+        player_query.get_single_mut()
+    else {
+        shake_events.clear();
+        landing_events.clear();
+        return;
+    };
+
+    //head bob, keeps time with the distance walked
+    let speed = Vec2::new(velocity.linvel.x, velocity.linvel.z).length();
+    let traversing = traversal.is_some_and(|traversal| !traversal.is_free());
+    let walking = ground.grounded && !player_health.dead && noclip.is_none() && !traversing;
+    let target_weight = if walking { (speed / movement.speed).min(1.) } else { 0. };
+    effects.bob_weight += (target_weight - effects.bob_weight) * (effects.bob_fade_speed * delta).min(1.);
+    if walking {
+        effects.bob_phase = (effects.bob_phase + speed * delta / effects.bob_stride * std::f32::consts::PI)
+            % (std::f32::consts::PI * 2.);
+    }
+    let bob = effects.bob_weight * settings.head_bob.amount();
+    //the head dips on every step and sways across once per two
+    let bob_offset = Vec3::new(
+        effects.bob_phase.sin() * effects.bob_sway,
+        -(effects.bob_phase.sin().abs()) * effects.bob_height,
+        0.,
+    ) * bob;
+    let bob_roll = effects.bob_phase.sin() * effects.bob_roll * bob;
+
+    //fov kick, only while actually running
+    let sprinting = sprint.is_some_and(|sprint| sprint.sprinting) && walking;
+    let target_kick = if sprinting { effects.sprint_fov_kick * target_weight } else { 0. };
+    effects.fov_kick += (target_kick - effects.fov_kick) * (effects.fov_kick_speed * delta).min(1.);
+
+    //screen shake
+    for ev in shake_events.iter() {
+        let falloff = ev.position.map_or(1., |position| {
+            (1. - position.distance(transform.translation) / ev.radius.max(0.01)).max(0.)
+        });
+        effects.trauma = (effects.trauma + ev.trauma * falloff).min(1.);
+    }
+    for landing in landing_events.iter() {
+        if landing.entity == entity && landing.speed > effects.landing_trauma_speed {
+            let trauma = (landing.speed - effects.landing_trauma_speed) * effects.landing_trauma;
+            effects.trauma = (effects.trauma + trauma).min(1.);
+        }
+    }
+    effects.trauma = (effects.trauma - effects.trauma_decay * delta).max(0.);
+    effects.shake_time += delta * effects.shake_frequency;
+    let shake = effects.trauma * effects.trauma * settings.screen_shake.amount();
+    let shake_angles = Vec3::new(
+        shake_noise(effects.shake_time, 0.) * effects.max_shake_angle,
+        shake_noise(effects.shake_time, 10.) * effects.max_shake_angle,
+        shake_noise(effects.shake_time, 20.) * effects.max_shake_roll,
+    ) * shake;
+
+    //aim punch, springs back on its own
+    let recovery = (-effects.punch_recovery * delta).exp();
+    effects.punch *= recovery;
+    let punch = effects.punch * settings.aim_punch.amount();
+
+    effects.angles = shake_angles + Vec3::new(punch.x, punch.y, bob_roll);
+    effects.offset = bob_offset;
+    effects.fov_offset = effects.fov_kick * settings.fov_kick.amount();
+}
+
+pub fn update_camera_effect_settings(
+    mut command_events: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    mut settings: ResMut<CameraEffectSettings>,
+) {
+    for command in command_events.iter() {
+        if command.name != "camera_effect" {
+            continue;
+        }
+        let args: Vec<&str> = command.args.iter().map(String::as_str).collect();
+        let Some(layer) = args.first().and_then(|name| settings.layer_mut(name)) else {
+            console.print("usage: camera_effect bob|fov|shake|punch on|off|<scale>");
+            continue;
+        };
+        match args.get(1) {
+            Some(&"on") => layer.enabled = true,
+            Some(&"off") => layer.enabled = false,
+            Some(value) => match value.parse::<f32>() {
+                Ok(scale) => {
+                    layer.scale = scale.max(0.);
+                    layer.enabled = true;
+                }
+                Err(_) => {
+                    console.print("usage: camera_effect bob|fov|shake|punch on|off|<scale>");
+                    continue;
+                }
+            },
+            None => {}
+        }
+        let layer = *layer;
+        console.print(format!(
+            "{} {} at {}",
+            args[0],
+            if layer.enabled { "on" } else { "off" },
+            layer.scale
+        ));
+        settings.save(CAMERA_EFFECTS_PATH);
+    }
+}
@@ -0,0 +1,202 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{console::{Console, ConsoleCommand}, fps_movement::{FPSMovement, GroundState}, hitbox::HITBOX_GROUP, input_map::{Action, ActionState}, noclip::Noclip, MapStatus};
+
+//how the player's body is moved, switched at runtime by changing the resource
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PlayerBodyMode {
+    //a locked-rotation dynamic rigid body pushed around by its velocity
+    #[default]
+    Dynamic,
+    //a kinematic body moved by rapier's character controller
+    Kinematic,
+}
+
+impl PlayerBodyMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PlayerBodyMode::Dynamic => "dynamic",
+            PlayerBodyMode::Kinematic => "kinematic",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct KinematicPlayer {
+    //carried between frames since rapier writes its own idea of the velocity back into Velocity
+    pub velocity: Vec3,
+}
+
+//half extents of the standing player's cuboid collider, everything probing around the player sizes itself from this
+pub const PLAYER_HALF_EXTENTS: Vec3 = Vec3::new(0.2, 1.4, 0.2);
+//surfaces steeper than this, in degrees, can't be walked up
+pub const MAX_SLOPE_CLIMB_ANGLE: f32 = 46.;
+//surfaces flatter than this, in degrees, are never slid down
+pub const MIN_SLOPE_SLIDE_ANGLE: f32 = 40.;
+pub const MAX_STEP_HEIGHT: f32 = 0.5;
+pub const SNAP_TO_GROUND_DISTANCE: f32 = 0.3;
+
+pub fn player_character_controller() -> KinematicCharacterController {
+    KinematicCharacterController {
+        up: Vec3::Y,
+        offset: CharacterLength::Absolute(0.02),
+        slide: true,
+        autostep: Some(CharacterAutostep {
+            max_height: CharacterLength::Absolute(MAX_STEP_HEIGHT),
+            min_width: CharacterLength::Absolute(0.2),
+            include_dynamic_bodies: false,
+        }),
+        max_slope_climb_angle: MAX_SLOPE_CLIMB_ANGLE.to_radians(),
+        min_slope_slide_angle: MIN_SLOPE_SLIDE_ANGLE.to_radians(),
+        apply_impulse_to_dynamic_bodies: true,
+        snap_to_ground: Some(CharacterLength::Absolute(SNAP_TO_GROUND_DISTANCE)),
+        filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
+        //walks through enemy hitboxes the same way the dynamic body does
+        filter_groups: Some(CollisionGroups::new(Group::ALL, Group::ALL - HITBOX_GROUP)),
+        ..default()
+    }
+}
+
+//swaps the player between the dynamic body and the character controller, by key or console command
+pub fn toggle_player_body_mode(
+    action_state: Res<ActionState>,
+    mut command_events: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    mut mode: ResMut<PlayerBodyMode>,
+) {
+    let mut new_mode = action_state.just_pressed(Action::BodyMode).then_some(match *mode {
+        PlayerBodyMode::Dynamic => PlayerBodyMode::Kinematic,
+        PlayerBodyMode::Kinematic => PlayerBodyMode::Dynamic,
+    });
+    for command in command_events.iter() {
+        if command.name != "body" {
+            continue;
+        }
+        match command.args.first().map(String::as_str) {
+            Some("dynamic") => new_mode = Some(PlayerBodyMode::Dynamic),
+            Some("kinematic") => new_mode = Some(PlayerBodyMode::Kinematic),
+            _ => console.print("usage: body dynamic|kinematic"),
+        }
+    }
+    if let Some(new_mode) = new_mode {
+        //only set on a real change, apply_player_body_mode rebuilds the body whenever the resource changes
+        mode.set_if_neq(new_mode);
+        console.print(format!("body {}", mode.label()));
+    }
+}
+
+pub fn apply_player_body_mode(
+    mut commands: Commands,
+    mode: Res<PlayerBodyMode>,
+    player_query: Query<(Entity, &Velocity, Option<&KinematicPlayer>), With<FPSMovement>>,
+) {
+    if !mode.is_changed() {
+        return;
+    }
+    for (entity, velocity, kinematic) in player_query.iter() {
+        match *mode {
+            PlayerBodyMode::Kinematic => {
+                commands.entity(entity).insert((
+                    RigidBody::KinematicPositionBased,
+                    player_character_controller(),
+                    KinematicPlayer {
+                        velocity: velocity.linvel,
+                    },
+                ));
+            }
+            PlayerBodyMode::Dynamic => {
+                let linvel = kinematic.map_or(velocity.linvel, |kinematic| kinematic.velocity);
+                commands
+                    .entity(entity)
+                    .insert((
+                        RigidBody::Dynamic,
+                        Velocity {
+                            linvel,
+                            angvel: Vec3::ZERO,
+                        },
+                    ))
+                    .remove::<(KinematicCharacterController, KinematicCharacterControllerOutput, KinematicPlayer)>();
+            }
+        }
+    }
+}
+
+//puts back the velocity the controller was really moving with, minus whatever last frame's move ran into
+pub fn restore_kinematic_velocity(
+    mut player_query: Query<(
+        &mut KinematicPlayer,
+        &mut Velocity,
+        Option<&KinematicCharacterControllerOutput>,
+    )>,
+) {
+    for (mut kinematic, mut velocity, output) in player_query.iter_mut() {
+        if let Some(output) = output {
+            if output.grounded && kinematic.velocity.y < 0. {
+                kinematic.velocity.y = 0.;
+            }
+            //bumped a ceiling
+            if output.desired_translation.y > 0. && output.effective_translation.y < output.desired_translation.y * 0.5 {
+                kinematic.velocity.y = 0.;
+            }
+            //lose the part of the velocity that went into walls
+            for collision in output.collisions.iter() {
+                let normal = collision.toi.normal2;
+                if normal.y.abs() > 0.7 {
+                    continue;
+                }
+                let into_wall = kinematic.velocity.dot(normal);
+                if into_wall < 0. {
+                    kinematic.velocity -= normal * into_wall;
+                }
+            }
+        }
+        velocity.linvel = kinematic.velocity;
+    }
+}
+
+//does what rapier would do for a dynamic body, damping and gravity, then hands the move to the character controller
+#[allow(clippy::type_complexity)]
+pub fn move_kinematic_player(
+    map_status: Res<MapStatus>,
+    fixed_time: Res<FixedTime>,
+    rapier_config: Res<RapierConfiguration>,
+    mut player_query: Query<
+        (
+            &mut KinematicPlayer,
+            &mut KinematicCharacterController,
+            &mut Velocity,
+            &Damping,
+            &GravityScale,
+            &GroundState,
+        ),
+        Without<Noclip>,
+    >,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    for (mut kinematic, mut controller, mut velocity, damping, gravity_scale, ground) in player_query.iter_mut() {
+        let mut linvel = velocity.linvel;
+        let horizontal_damping = 1. / (1. + delta * damping.linear_damping);
+        linvel.x *= horizontal_damping;
+        linvel.z *= horizontal_damping;
+
+        //standing still on a slope doesn't build up any downward speed to slide with
+        if ground.grounded && linvel.y <= 0. {
+            linvel.y = 0.;
+        } else {
+            linvel.y += rapier_config.gravity.y * gravity_scale.0 * delta;
+        }
+
+        kinematic.velocity = linvel;
+        velocity.linvel = linvel;
+        if !map_status.loaded {
+            continue;
+        }
+        let mut translation = linvel * delta;
+        //keeps the controller reporting ground contact, snapping stops it becoming an actual fall
+        if ground.grounded && linvel.y <= 0. {
+            translation.y -= 0.01;
+        }
+        controller.translation = Some(translation);
+    }
+}
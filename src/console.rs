@@ -0,0 +1,157 @@
+use bevy::prelude::*;
+
+use crate::{
+    input_map::{Action, ActionState},
+    lock_cursor::CursorLockState,
+};
+
+//lines of output kept on screen
+const MAX_LOG_LINES: usize = 12;
+
+//everything the console knows how to run, the first word is the command name
+pub const COMMANDS: [(&str, &str); 16] = [
+    ("help", "lists the commands"),
+    ("noclip", "toggles flying through walls"),
+    ("noclip_speed <speed>", "sets the noclip flight speed"),
+    ("spectate", "toggles the spectator camera"),
+    ("spectate free|follow|orbit", "switches the spectator camera mode"),
+    ("spectate next", "moves the spectator camera on to the next enemy"),
+    ("movement classic|source|quake", "switches the player movement model"),
+    ("body dynamic|kinematic", "switches the player between a dynamic body and the character controller"),
+    ("friendly_fire off|reduced|full [scale]", "sets how much damage teammates do to each other"),
+    ("sensitivity <value>", "sets the in-game sensitivity of the current preset, or shows it in every game"),
+    ("sensitivity_preset source|valorant|overwatch|apex|custom", "picks which game's sensitivity numbers to use"),
+    ("degrees_per_count <degrees>", "sets the turn per mouse count directly"),
+    ("sensitivity_axes <yaw> <pitch>", "sets the horizontal and vertical multipliers"),
+    ("ads_sensitivity <distance> [multiplier]", "monitor distance from 0 to 1 matched when aiming down sights"),
+    ("mouse_accel off|linear|power [rate] [cap]", "sets the mouse acceleration curve"),
+    ("camera_effect bob|fov|shake|punch on|off|<scale>", "turns a camera effect on or off or scales it"),
+];
+
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub log: Vec<String>,
+}
+
+impl Console {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+}
+
+//sent when a line is entered, whichever system owns the command picks it up
+pub struct ConsoleCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Component)]
+pub struct ConsoleRoot;
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+pub fn spawn_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let text_style = TextStyle {
+        font: asset_server.load("font.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(35.0)),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexEnd,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.05, 0.05, 0.05, 0.85).into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ConsoleRoot,
+        ))
+        .with_children(|parent| {
+            //the log, then the line being typed
+            parent.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("", text_style.clone()),
+                    TextSection::new("> ", text_style),
+                ]),
+                ConsoleText,
+            ));
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_console(
+    action_state: Res<ActionState>,
+    key: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut console: ResMut<Console>,
+    mut cursor_lock_state: ResMut<CursorLockState>,
+    mut command_events: EventWriter<ConsoleCommand>,
+    mut root_query: Query<&mut Visibility, With<ConsoleRoot>>,
+    mut text_query: Query<&mut Text, With<ConsoleText>>,
+) {
+    if action_state.just_pressed(Action::Console) || (console.open && key.just_pressed(KeyCode::Escape)) {
+        console.open = !console.open;
+        if console.open {
+            cursor_lock_state.state = false;
+        }
+        //the key that opened the console would otherwise be typed into it
+        characters.clear();
+    }
+    for mut visibility in root_query.iter_mut() {
+        *visibility = if console.open {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+    if !console.open {
+        characters.clear();
+        return;
+    }
+
+    for character in characters.iter() {
+        if !character.char.is_control() {
+            console.input.push(character.char);
+        }
+    }
+    if key.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if key.just_pressed(KeyCode::Return) || key.just_pressed(KeyCode::NumpadEnter) {
+        let line = std::mem::take(&mut console.input);
+        let mut words = line.split_whitespace().map(str::to_string);
+        if let Some(name) = words.next() {
+            console.print(format!("> {}", line.trim()));
+            if name == "help" {
+                for (usage, description) in COMMANDS {
+                    console.print(format!("{} - {}", usage, description));
+                }
+            } else if COMMANDS.iter().any(|(usage, _)| usage.split(' ').next() == Some(name.as_str())) {
+                command_events.send(ConsoleCommand {
+                    name,
+                    args: words.collect(),
+                });
+            } else {
+                console.print(format!("unknown command {}, try help", name));
+            }
+        }
+    }
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = console.log.iter().map(|line| format!("{}\n", line)).collect();
+        text.sections[1].value = format!("> {}", console.input);
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    gun_control::GunController,
+    input_map::{Action, ActionState},
+    noclip::Noclip,
+    player_health::PlayerHealth,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CrouchMode {
+    //crouched for as long as the key is held
+    Hold,
+    //each press flips between crouching and standing
+    Toggle,
+}
+
+#[derive(Component)]
+pub struct Crouch {
+    pub mode: CrouchMode,
+    //whether the player wants to be crouched, they stay down anyway while something is overhead
+    pub wants_crouch: bool,
+    //0 is standing, 1 is fully crouched
+    pub amount: f32,
+    //seconds to go all the way down or up
+    pub transition_time: f32,
+    //half heights of the player's cuboid collider, the camera sits at its centre
+    pub stand_half_height: f32,
+    pub crouch_half_height: f32,
+    pub half_width: f32,
+    //applied to FPSMovement::speed when fully crouched
+    pub speed_multiplier: f32,
+    //applied to weapon spread when fully crouched
+    pub spread_multiplier: f32,
+}
+
+impl Crouch {
+    pub fn new(mode: CrouchMode, stand_half_height: f32, half_width: f32) -> Self {
+        Crouch {
+            mode,
+            wants_crouch: false,
+            amount: 0.,
+            transition_time: 0.15,
+            stand_half_height,
+            crouch_half_height: stand_half_height * 0.6,
+            half_width,
+            speed_multiplier: 0.5,
+            spread_multiplier: 0.6,
+        }
+    }
+
+    pub fn half_height(&self) -> f32 {
+        self.half_height_at(self.amount)
+    }
+
+    pub fn half_height_at(&self, amount: f32) -> f32 {
+        self.stand_half_height + (self.crouch_half_height - self.stand_half_height) * amount
+    }
+
+    pub fn speed_scale(&self) -> f32 {
+        1. + (self.speed_multiplier - 1.) * self.amount
+    }
+
+    pub fn spread_scale(&self) -> f32 {
+        1. + (self.spread_multiplier - 1.) * self.amount
+    }
+}
+
+//true if there is nothing above the player's head for them to grow into
+fn has_headroom(
+    rapier_context: &RapierContext,
+    player: Entity,
+    transform: &Transform,
+    crouch: &Crouch,
+    rise: f32,
+) -> bool {
+    //a thin slab at the top of the collider swept upwards, slightly narrower so walls next to the player don't count
+    let slab_half_height = 0.02;
+    let slab = Collider::cuboid(crouch.half_width * 0.95, slab_half_height, crouch.half_width * 0.95);
+    let slab_position = transform.translation + Vec3::Y * (crouch.half_height() - slab_half_height);
+    let filter = QueryFilter::new().exclude_rigid_body(player).exclude_sensors();
+    rapier_context
+        .cast_shape(slab_position, Quat::IDENTITY, Vec3::Y, &slab, rise, filter)
+        .is_none()
+}
+
+//grows or shrinks the collider around the player's feet, which lowers and raises the camera with it
+pub fn update_crouch(
+    fixed_time: Res<FixedTime>,
+    action_state: Res<ActionState>,
+    rapier_context: Res<RapierContext>,
+    mut player_query: Query<
+        (Entity, &mut Transform, &mut Collider, &mut Crouch, &PlayerHealth),
+        Without<Noclip>,
+    >,
+) {
+    for (entity, mut transform, mut collider, mut crouch, player_health) in player_query.iter_mut() {
+        if player_health.dead {
+            crouch.wants_crouch = false;
+        } else {
+            match crouch.mode {
+                CrouchMode::Hold => crouch.wants_crouch = action_state.pressed(Action::Crouch),
+                CrouchMode::Toggle => {
+                    if action_state.pressed_this_tick(Action::Crouch) {
+                        crouch.wants_crouch = !crouch.wants_crouch;
+                    }
+                }
+            }
+        }
+
+        let target = if crouch.wants_crouch { 1. } else { 0. };
+        let step = fixed_time.period.as_secs_f32() / crouch.transition_time;
+        let new_amount = if target > crouch.amount {
+            (crouch.amount + step).min(target)
+        } else {
+            (crouch.amount - step).max(target)
+        };
+        if new_amount == crouch.amount {
+            continue;
+        }
+
+        let old_half_height = crouch.half_height();
+        let new_half_height = crouch.half_height_at(new_amount);
+        //stay down until the ceiling is gone
+        if new_half_height > old_half_height
+            && !has_headroom(&rapier_context, entity, &transform, &crouch, (new_half_height - old_half_height) * 2.)
+        {
+            continue;
+        }
+
+        crouch.amount = new_amount;
+        //keep the feet where they were
+        transform.translation.y += new_half_height - old_half_height;
+        *collider = Collider::cuboid(crouch.half_width, new_half_height, crouch.half_width);
+    }
+}
+
+pub fn apply_crouch_spread(
+    mut gun_query: Query<&mut GunController>,
+    crouch_query: Query<&Crouch>,
+) {
+    let Ok(crouch) = crouch_query.get_single() else {
+        return;
+    };
+    for mut gun_controller in gun_query.iter_mut() {
+        gun_controller.spread_multiplier = crouch.spread_scale();
+    }
+}
@@ -1,6 +1,19 @@
-use crate::{fps_movement::FPSMovement, AnimationEntityLink, EnemyAnimations};
-use bevy::{prelude::*, render::view::NoFrustumCulling};
+use crate::{
+    camera_effects::{CameraShake, NEAR_MISS_RADIUS, NEAR_MISS_TRAUMA},
+    enemy_ai::{EnemyBehavior, EnemyState, EnemyStimuli, DIE_ANIMATION},
+    enemy_perception::{Perception, EYE_HEIGHT},
+    hitbox::Hitbox,
+    navmesh::NavAgent,
+    player_health::PlayerDamageEvent,
+    player_health::PlayerHealth,
+    ragdoll::{EnemyKilledEvent, Ragdoll},
+    squad::{SquadMember, SquadRole, Squads},
+    team::{FriendlyFire, Team},
+    AnimationEntityLink, EnemyAnimations,
+};
+use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
+use rand::Rng;
 
 #[derive(Component)]
 pub struct Enemy {
@@ -8,144 +21,335 @@ pub struct Enemy {
     pub shoot_timer: f32,
     pub shoot_cooldown: f32,
     pub health : f32,
-    pub respawn_timer : f32,
-    pub respawned : bool,
+    pub armor: f32,
+    //fraction of incoming damage soaked up by armor while any is left
+    pub armor_absorption: f32,
+    //seconds a dead enemy's body stays around before it is despawned
+    pub corpse_timer : f32,
+    pub died : bool,
+    pub shot_damage: f32,
+    pub attack_range: f32,
+    //chance each shot with a clear line to the player hits, 0 to 1
+    pub accuracy: f32,
 }
-#[derive(Component)]
-pub struct HeadCollider {
-    pub enemy_reference: Entity,
+
+impl Enemy {
+    //returns true if this hit killed the enemy
+    pub fn take_damage(&mut self, amount: f32) -> bool {
+        if self.health <= 0. || amount <= 0. {
+            return false;
+        }
+        let absorbed = f32::min(amount * self.armor_absorption, self.armor);
+        self.armor -= absorbed;
+        self.health -= amount - absorbed;
+        self.health <= 0.
+    }
 }
 #[derive(Component)]
-pub struct BodyCollider {
-    pub enemy_reference: Entity,
+pub struct EnemyFacing {
+    //degrees per second
+    pub turn_rate: f32,
+    //the yaw the enemy was placed with, returned to when it has nothing else to look at
+    pub home_yaw: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    //degrees either side of level the upper body can aim
+    pub max_pitch: f32,
+    pub aim_bone_name: String,
+    pub aim_bone: Option<Entity>,
+    //the bone's rotation before the aim was added, and the rotation written with it last frame
+    pub aim_bone_base: Quat,
+    pub aim_bone_written: Option<Quat>,
 }
-#[derive(Component)]
-pub struct LegCollider {
-    pub enemy_reference: Entity,
+
+impl EnemyFacing {
+    pub fn new(home_yaw: f32, turn_rate: f32) -> Self {
+        EnemyFacing {
+            turn_rate,
+            home_yaw,
+            yaw: home_yaw,
+            pitch: 0.,
+            max_pitch: 60.,
+            aim_bone_name: "Body".to_string(),
+            aim_bone: None,
+            aim_bone_base: Quat::IDENTITY,
+            aim_bone_written: None,
+        }
+    }
+}
+
+//impulse an enemy's shot gives the ragdoll of another enemy it kills
+const SHOT_IMPULSE: f32 = 20.;
+
+//the person model looks down its local +z axis
+pub const MODEL_FORWARD: Vec3 = Vec3::Z;
+
+pub type EnemyHitboxFilter = With<Hitbox>;
+
+//true if nothing but enemy hitboxes is between the origin and the target entity
+pub fn can_see_entity(
+    rapier_context: &RapierContext,
+    origin: Vec3,
+    target: Vec3,
+    target_entity: Entity,
+    hitbox_query: &Query<(), EnemyHitboxFilter>,
+) -> bool {
+    let to_target = target - origin;
+    let not_hitbox = |collider: Entity| !hitbox_query.contains(collider);
+    let filter = QueryFilter::new().exclude_sensors().predicate(&not_hitbox);
+    match rapier_context.cast_ray(origin, to_target.normalize(), to_target.length(), true, filter) {
+        Some((hit_entity, _toi)) => hit_entity == target_entity,
+        None => true,
+    }
+}
+
+pub fn find_descendant_by_name(
+    entity: Entity,
+    name: &str,
+    children_query: &Query<&Children>,
+    name_query: &Query<&Name>,
+) -> Option<Entity> {
+    let children = children_query.get(entity).ok()?;
+    for child in children.iter() {
+        if name_query.get(*child).is_ok_and(|n| n.as_str() == name) {
+            return Some(*child);
+        }
+        if let Some(found) = find_descendant_by_name(*child, name, children_query, name_query) {
+            return Some(found);
+        }
+    }
+    None
 }
 
-pub fn rotate_to_player(
+pub fn update_enemies(
+    mut commands: Commands,
     enemy_animations: Res<EnemyAnimations>,
     mut animation_player_query: Query<&mut AnimationPlayer>,
-    player_query: Query<(&Transform, &FPSMovement)>,
-    mut commands: Commands,
-    mut enemy_query: Query<(&mut Enemy, Entity, &Children, &AnimationEntityLink)>,
-    get_child_query: Query<&Children>,
-    name_query: Query<&Name>,
-    _transform_query: Query<&mut Transform, Without<FPSMovement>>,
+    mut enemy_query: Query<(Entity, &mut Enemy, &AnimationEntityLink, Option<&Ragdoll>)>,
     time : Res<Time>,
 ) {
-    if let Ok((_player_transform, _movement)) = player_query.get_single() {
-        for (mut enemy, entity, child, animation_entity) in enemy_query.iter_mut() {
-            enemy.respawn_timer-=time.delta_seconds();
-            
+    for (entity, mut enemy, animation_entity, ragdoll) in enemy_query.iter_mut() {
+        enemy.corpse_timer-=time.delta_seconds();
+
+        if enemy.health <=0. && !enemy.died
+        {
             if let Ok(mut player) = animation_player_query.get_mut(animation_entity.0) {
-                if enemy.health <=0. && enemy.respawned
-                {
-                    player.play(enemy_animations.0[1].clone_weak());
-                    enemy.respawn_timer = 10.;
-                    enemy.respawned = false;
-                }
-                else if enemy.respawned
-                {
-                    player.play(enemy_animations.0[0].clone_weak()).repeat();
-                }
-                
+                player.play(enemy_animations.0[DIE_ANIMATION].clone_weak()).set_speed(1.);
             }
-            //println!("{} {}",enemy.health,enemy.respawn_timer);
-            if enemy.health <=0. && !enemy.respawned
-            {
-                if enemy.respawn_timer <=0.
-                {
-                    enemy.health = 100.;
-                    enemy.respawned = true;
+            enemy.corpse_timer = 10.;
+            enemy.died = true;
+        }
+        //the spawn director brings in new enemies, so bodies are cleared away rather than respawned
+        if enemy.died && enemy.corpse_timer <=0.
+        {
+            if let Some(ragdoll) = ragdoll {
+                for link in ragdoll.links.iter() {
+                    commands.entity(link.body).despawn_recursive();
                 }
             }
-            
-            if enemy.added_colliders == false {
-                if let Ok(child_of_child) = get_child_query.get(child[0]) {
-                    if let Ok(child_of_child_of_child) = get_child_query.get(child_of_child[0]) {
-                        for i in 0..child_of_child_of_child.len() {
-                            if let Ok(name) = name_query.get(child_of_child_of_child[i]) {
-                                //println!("{} {}",child_of_child_of_child.len(),name.as_str());
-                                if name.as_str() == "Cube"
-                                {
-                                    commands.entity(child_of_child_of_child[i]).insert(NoFrustumCulling);
-                                    if let Ok(cube_children) = get_child_query.get(child_of_child_of_child[i]) {
-                                        for k in 0..cube_children.len()
-                                        {
-                                           commands.entity(cube_children[k]).insert(NoFrustumCulling);
-                                        }
-                                    }
-                                }
-                                else if name.as_str() == "Rear" {
-                                    let collider_entity_body = commands
-                                    .spawn(Collider::cuboid(0.5, 1.225, 0.5))
-                                    .insert(TransformBundle {
-                                        local: Transform::from_xyz(0., -1.55, 0.),
-                                        ..default()
-                                    })
-                                    .insert(ColliderDebugColor(Color::RED))
-                                    .insert(LegCollider {
-                                        enemy_reference: entity,
-                                    })
-                                    .id();
-                                    commands
-                                        .entity(child_of_child_of_child[i])
-                                        .push_children(&[collider_entity_body]);
-                                    }
-                                else if name.as_str() == "Body" {
-                                    let collider_entity_body = commands
-                                        .spawn(Collider::cuboid(0.5, 1.5, 0.5))
-                                        .insert(TransformBundle {
-                                            local: Transform::from_xyz(0., 0.8, 0.),
-                                            ..default()
-                                        })
-                                        
-                                        .insert(ColliderDebugColor(Color::BLUE))
-                                        .insert(BodyCollider {
-                                            enemy_reference: entity,
-                                        })
-                                        .id();
-                                    commands
-                                        .entity(child_of_child_of_child[i])
-                                        .push_children(&[collider_entity_body]);
-
-                                    if let Ok(child_of_child_of_child_body_child) =
-                                        get_child_query.get(child_of_child_of_child[i])
-                                    {
-                                        for j in 0..child_of_child_of_child_body_child.len() {
-                                            if let Ok(body_child_name) = name_query
-                                                .get(child_of_child_of_child_body_child[j])
-                                            {
-                                                if body_child_name.as_str() == "Head" {
-                                                    let collider_entity = commands
-                                                        .spawn(Collider::cuboid(0.2, 0.4, 0.2))
-                                                        .insert(TransformBundle {
-                                                            local: Transform::from_xyz(0., 0.5, 0.),
-                                                            ..default()
-                                                        })
-                                                        .insert(HeadCollider {
-                                                            enemy_reference: entity,
-                                                        })
-                                                        .insert(ColliderDebugColor(Color::GREEN))
-                                                        .id();
-                                                    commands
-                                                        .entity(
-                                                            child_of_child_of_child_body_child[j],
-                                                        )
-                                                        .push_children(&[collider_entity]);
-                                                    enemy.added_colliders = true;
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub struct EnemyDamageEvent {
+    pub enemy: Entity,
+    pub amount: f32,
+    pub team: Team,
+    pub direction: Vec3,
+    pub attacker_position: Vec3,
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn shoot_at_target(
+    fixed_time: Res<FixedTime>,
+    rapier_context: Res<RapierContext>,
+    target_query: Query<(&Transform, Option<&PlayerHealth>), Without<Enemy>>,
+    mut enemy_query: Query<(
+        Entity,
+        &mut Enemy,
+        &Transform,
+        &Team,
+        &Perception,
+        &EnemyBehavior,
+        &EnemyStimuli,
+        Option<&SquadMember>,
+        Option<&Name>,
+    )>,
+    bone_query: Query<&GlobalTransform>,
+    hitbox_query: Query<(), EnemyHitboxFilter>,
+    mut squads: ResMut<Squads>,
+    mut player_damage_events: EventWriter<PlayerDamageEvent>,
+    mut enemy_damage_events: EventWriter<EnemyDamageEvent>,
+    mut shake_events: EventWriter<CameraShake>,
+) {
+    //enemies can't be looked up in enemy_query while it is being iterated, so note where they all are first
+    let enemy_positions: Vec<(Entity, Vec3)> = enemy_query
+        .iter()
+        .map(|(entity, _, transform, ..)| (entity, transform.translation + Vec3::Y * EYE_HEIGHT))
+        .collect();
+    for (_, mut enemy, transform, team, perception, behavior, stimuli, squad_member, name) in enemy_query.iter_mut() {
+        if !behavior.state.can_fire() || !stimuli.can_see_target {
+            //give the target a moment after being spotted before the first shot
+            enemy.shoot_timer = f32::max(enemy.shoot_timer, behavior.params.reaction_time);
+            continue;
+        }
+        enemy.shoot_timer -= fixed_time.period.as_secs_f32();
+        if enemy.shoot_timer > 0. {
+            continue;
+        }
+        let Some(target) = stimuli.target else {
+            continue;
+        };
+        let (target_position, target_is_player) = match target_query.get(target) {
+            Ok((target_transform, player_health)) => (target_transform.translation, player_health.is_some()),
+            Err(_) => match enemy_positions.iter().find(|(entity, _)| *entity == target) {
+                Some((_, position)) => (*position, false),
+                None => continue,
+            },
+        };
+        //wait for a squadmate's shot to be far enough behind
+        if squad_member.is_some_and(|member| !squads.can_fire(member.squad)) {
+            continue;
+        }
+        let origin = perception
+            .head
+            .and_then(|head| bone_query.get(head).ok())
+            .map_or(transform.translation + Vec3::Y * EYE_HEIGHT, |head| head.translation());
+        if origin.distance(target_position) > enemy.attack_range {
+            continue;
+        }
+        if !can_see_entity(&rapier_context, origin, target_position, target, &hitbox_query) {
+            continue;
+        }
+        //the shot goes off here, hit or miss, so only now does it use up the cooldown and the squad's turn
+        let suppressing = squad_member.is_some_and(|member| member.role == SquadRole::Suppress);
+        enemy.shoot_timer = if suppressing { enemy.shoot_cooldown * 0.6 } else { enemy.shoot_cooldown };
+        if let Some(member) = squad_member {
+            squads.fired(member.squad);
+        }
+        if rand::thread_rng().gen::<f32>() >= if suppressing { enemy.accuracy * 0.5 } else { enemy.accuracy } {
+            //a miss still cracks past the player's head
+            if target_is_player {
+                shake_events.send(CameraShake {
+                    position: Some(target_position),
+                    trauma: NEAR_MISS_TRAUMA,
+                    radius: NEAR_MISS_RADIUS,
+                });
+            }
+            continue;
+        }
+        let source = name.map_or("Enemy".to_string(), |n| n.to_string());
+        if target_is_player {
+            player_damage_events.send(PlayerDamageEvent {
+                amount: enemy.shot_damage,
+                source,
+                team: Some(*team),
+            });
+        } else {
+            enemy_damage_events.send(EnemyDamageEvent {
+                enemy: target,
+                amount: enemy.shot_damage,
+                team: *team,
+                direction: (target_position - origin).normalize_or_zero(),
+                attacker_position: transform.translation,
+            });
+        }
+    }
+}
+
+pub fn apply_enemy_damage(
+    friendly_fire: Res<FriendlyFire>,
+    mut damage_events: EventReader<EnemyDamageEvent>,
+    mut enemy_query: Query<(&mut Enemy, &Team)>,
+    mut killed_events: EventWriter<EnemyKilledEvent>,
+) {
+    for event in damage_events.iter() {
+        let Ok((mut enemy, team)) = enemy_query.get_mut(event.enemy) else {
+            continue;
+        };
+        if enemy.take_damage(event.amount * friendly_fire.damage_scale(event.team, *team)) {
+            killed_events.send(EnemyKilledEvent {
+                enemy: event.enemy,
+                bone: None,
+                impulse: event.direction * SHOT_IMPULSE,
+                team: event.team,
+                attacker_position: event.attacker_position,
+            });
+        }
+    }
+}
+
+pub fn yaw_towards(direction: Vec3) -> f32 {
+    f32::atan2(direction.x, direction.z)
+}
+
+//steps current towards target along the shorter way around the circle
+pub fn turn_towards(current: f32, target: f32, max_delta: f32) -> f32 {
+    let difference = (target - current + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+        - std::f32::consts::PI;
+    current + difference.clamp(-max_delta, max_delta)
+}
+
+pub fn rotate_to_target(
+    fixed_time: Res<FixedTime>,
+    mut enemy_query: Query<(&mut Transform, &mut EnemyFacing, &EnemyBehavior, &EnemyStimuli, &NavAgent)>,
+) {
+    for (mut transform, mut facing, behavior, stimuli, agent) in enemy_query.iter_mut() {
+        let max_delta = facing.turn_rate.to_radians() * fixed_time.period.as_secs_f32();
+        if behavior.state == EnemyState::Dead {
+            facing.pitch = 0.;
+            continue;
+        }
+        let position = transform.translation;
+        let (target_yaw, target_pitch) = if let Some(target) = stimuli.target_position {
+            let to_target = target - (position + Vec3::Y * EYE_HEIGHT);
+            let horizontal = Vec2::new(to_target.x, to_target.z).length();
+            let pitch = if stimuli.can_see_target {
+                f32::atan2(to_target.y, horizontal)
+            } else {
+                0.
+            };
+            (yaw_towards(to_target), pitch)
+        } else if let Some(waypoint) = agent.path.first() {
+            (yaw_towards(*waypoint - position), 0.)
+        } else {
+            (facing.home_yaw, 0.)
+        };
+
+        facing.yaw = turn_towards(facing.yaw, target_yaw, max_delta);
+        let max_pitch = facing.max_pitch.to_radians();
+        facing.pitch = turn_towards(facing.pitch, target_pitch.clamp(-max_pitch, max_pitch), max_delta);
+        transform.rotation = Quat::from_rotation_y(facing.yaw);
+    }
+}
+
+//runs after the animation has posed the skeleton so the aim bends the upper body on top of it
+pub fn apply_aim_pitch(
+    mut enemy_query: Query<(Entity, &Transform, &mut EnemyFacing)>,
+    children_query: Query<&Children>,
+    name_query: Query<&Name>,
+    mut bone_query: Query<(&mut Transform, &GlobalTransform), Without<EnemyFacing>>,
+) {
+    for (entity, transform, mut facing) in enemy_query.iter_mut() {
+        if facing.aim_bone.is_none() {
+            facing.aim_bone = find_descendant_by_name(entity, &facing.aim_bone_name, &children_query, &name_query);
+        }
+        let Some(aim_bone) = facing.aim_bone else {
+            continue;
+        };
+        if let Ok((mut bone_transform, bone_global)) = bone_query.get_mut(aim_bone) {
+            //a clip that keys the bone has already put it back this frame, one that doesn't leaves last frame's aim
+            //on it, so the aim always goes on top of the pose without it rather than piling up
+            if facing.aim_bone_written != Some(bone_transform.rotation) {
+                facing.aim_bone_base = bone_transform.rotation;
             }
+            let forward = transform.rotation * MODEL_FORWARD;
+            let world_axis = forward.cross(Vec3::Y).normalize();
+            let (_, bone_rotation, _) = bone_global.to_scale_rotation_translation();
+            let local_axis = (bone_rotation.inverse() * world_axis).normalize();
+            bone_transform.rotation = facing.aim_bone_base * Quat::from_axis_angle(local_axis, facing.pitch);
+            facing.aim_bone_written = Some(bone_transform.rotation);
         }
     }
 }
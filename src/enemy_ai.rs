@@ -0,0 +1,505 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+use crate::{
+    enemy::{Enemy, EnemyHitboxFilter},
+    navmesh::{NavAgent, NavMesh},
+    squad::{SquadMember, SquadRole},
+    AnimationEntityLink, EnemyAnimations,
+};
+
+//indices into EnemyAnimations
+pub const ALERT_ANIMATION: usize = 0;
+pub const DIE_ANIMATION: usize = 1;
+
+//how high above the floor a crouching and a standing enemy shoots from
+const COVER_LOW_HEIGHT: f32 = 0.7;
+const COVER_HIGH_HEIGHT: f32 = 1.4;
+//after a search finds no cover, wait this long or for the threat to move this far before trying again
+const COVER_RETRY_TIME: f32 = 1.;
+const COVER_RETRY_DISTANCE: f32 = 3.;
+
+//how far to the side of the target a flanking squad member tries to get
+const FLANK_DISTANCE: f32 = 8.;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EnemyState {
+    Idle,
+    Patrol,
+    Alert,
+    Chase,
+    TakeCover,
+    Attack,
+    Retreat,
+    Dead,
+}
+
+impl EnemyState {
+    pub fn can_fire(&self) -> bool {
+        matches!(self, EnemyState::Attack | EnemyState::Retreat)
+    }
+}
+
+//fields left out of an archetype file keep their default
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct BehaviorParams {
+    pub idle_time: f32,
+    pub reaction_time: f32,
+    pub attack_range: f32,
+    //fraction of max health below which the enemy falls back
+    pub retreat_health: f32,
+    pub use_cover: bool,
+    pub cover_search_radius: f32,
+    pub patrol_radius: f32,
+}
+
+impl Default for BehaviorParams {
+    fn default() -> Self {
+        BehaviorParams {
+            idle_time: 3.,
+            reaction_time: 0.6,
+            attack_range: 25.,
+            retreat_health: 0.3,
+            use_cover: true,
+            cover_search_radius: 8.,
+            patrol_radius: 8.,
+        }
+    }
+}
+
+//what the enemy currently knows about its target, written by the perception systems
+#[derive(Component, Default)]
+pub struct EnemyStimuli {
+    //the player or enemy of another team last picked as the target
+    pub target: Option<Entity>,
+    pub can_see_target: bool,
+    //last known position, gone once the memory of it fades
+    pub target_position: Option<Vec3>,
+    pub certainty: f32,
+}
+
+#[derive(Component)]
+pub struct EnemyBehavior {
+    pub state: EnemyState,
+    pub state_time: f32,
+    pub params: BehaviorParams,
+    pub max_health: f32,
+    pub home: Vec3,
+    pub patrol_points: Vec<Vec3>,
+    pub patrol_index: usize,
+    pub cover_point: Option<Vec3>,
+    pub cover_retry_timer: f32,
+    //where the threat was when the last search came up empty
+    pub failed_cover_threat: Option<Vec3>,
+    pub retreat_point: Option<Vec3>,
+    pub flank_point: Option<Vec3>,
+}
+
+impl EnemyBehavior {
+    pub fn new(home: Vec3, max_health: f32, params: BehaviorParams) -> Self {
+        EnemyBehavior {
+            state: EnemyState::Idle,
+            state_time: 0.,
+            params,
+            max_health,
+            home,
+            patrol_points: Vec::new(),
+            patrol_index: 0,
+            cover_point: None,
+            cover_retry_timer: 0.,
+            failed_cover_threat: None,
+            retreat_point: None,
+            flank_point: None,
+        }
+    }
+}
+
+pub struct BehaviorContext {
+    pub alive: bool,
+    pub can_see_target: bool,
+    pub knows_target: bool,
+    pub distance_to_target: f32,
+    pub health_fraction: f32,
+    //the nav agent has nowhere left to walk
+    pub arrived: bool,
+    pub has_patrol: bool,
+    pub has_cover: bool,
+}
+
+pub fn next_state(
+    state: EnemyState,
+    state_time: f32,
+    params: &BehaviorParams,
+    ctx: &BehaviorContext,
+) -> EnemyState {
+    use EnemyState::*;
+    //dead enemies are never brought back, the spawn director sends in new ones instead
+    if state == Dead || !ctx.alive {
+        return Dead;
+    }
+    let in_range = ctx.can_see_target && ctx.distance_to_target <= params.attack_range;
+    match state {
+        Idle | Patrol if ctx.knows_target => Alert,
+        Idle if ctx.has_patrol && state_time >= params.idle_time => Patrol,
+        Patrol if ctx.arrived && state_time > 0.1 => Idle,
+        Idle | Patrol => state,
+        _ if !ctx.knows_target => Idle,
+        Retreat => Retreat,
+        _ if ctx.health_fraction <= params.retreat_health => Retreat,
+        Alert if state_time < params.reaction_time => Alert,
+        //give the nav agent a moment to find a path before checking if it arrived
+        TakeCover if !ctx.arrived || state_time < 0.2 => TakeCover,
+        Alert | Chase if in_range && params.use_cover && ctx.has_cover => TakeCover,
+        _ if in_range => Attack,
+        _ => Chase,
+    }
+}
+
+//which clip each state plays, and how fast
+pub fn state_animation(state: EnemyState) -> (usize, f32) {
+    match state {
+        EnemyState::Idle => (ALERT_ANIMATION, 0.5),
+        EnemyState::Patrol => (ALERT_ANIMATION, 0.8),
+        EnemyState::Alert => (ALERT_ANIMATION, 1.0),
+        EnemyState::Chase => (ALERT_ANIMATION, 1.5),
+        EnemyState::TakeCover => (ALERT_ANIMATION, 1.5),
+        EnemyState::Attack => (ALERT_ANIMATION, 1.0),
+        EnemyState::Retreat => (ALERT_ANIMATION, 1.5),
+        EnemyState::Dead => (DIE_ANIMATION, 1.0),
+    }
+}
+
+//looks for a spot nearby where a crouching enemy is hidden from the threat but can still shoot standing up
+pub fn find_cover(
+    navmesh: &NavMesh,
+    rapier_context: &RapierContext,
+    hitbox_query: &Query<(), EnemyHitboxFilter>,
+    position: Vec3,
+    threat: Vec3,
+    radius: f32,
+) -> Option<Vec3> {
+    let candidates = navmesh.reachable_points(position, radius);
+    let stride = usize::max(candidates.len() / 64, 1);
+    let not_hitbox = |collider: Entity| !hitbox_query.contains(collider);
+    let filter = QueryFilter::only_fixed().predicate(&not_hitbox);
+    let blocked = |from: Vec3, to: Vec3| {
+        let direction = to - from;
+        rapier_context
+            .cast_ray(from, direction.normalize(), direction.length(), true, filter)
+            .is_some()
+    };
+    candidates
+        .iter()
+        .step_by(stride)
+        .filter(|point| {
+            blocked(threat, **point + Vec3::Y * COVER_LOW_HEIGHT)
+                && !blocked(**point + Vec3::Y * COVER_HIGH_HEIGHT, threat)
+        })
+        .min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)))
+        .copied()
+}
+
+//a reachable spot off to one side of the line between the enemy and its target
+pub fn find_flank_point(navmesh: &NavMesh, position: Vec3, target: Vec3, left: bool) -> Option<Vec3> {
+    let to_target = Vec3::new(target.x - position.x, 0., target.z - position.z).normalize_or_zero();
+    let side = if left { Vec3::Y.cross(to_target) } else { to_target.cross(Vec3::Y) };
+    let ideal = target + side * FLANK_DISTANCE;
+    navmesh
+        .reachable_points(target, FLANK_DISTANCE)
+        .into_iter()
+        .min_by(|a, b| a.distance(ideal).total_cmp(&b.distance(ideal)))
+}
+
+#[allow(clippy::type_complexity)]
+pub fn update_enemy_behavior(
+    fixed_time: Res<FixedTime>,
+    navmesh: Option<Res<NavMesh>>,
+    rapier_context: Res<RapierContext>,
+    mut enemy_query: Query<(
+        Entity,
+        &Enemy,
+        &mut EnemyBehavior,
+        &EnemyStimuli,
+        &mut NavAgent,
+        &Transform,
+        Option<&SquadMember>,
+    )>,
+    hitbox_query: Query<(), EnemyHitboxFilter>,
+) {
+    let mut rng = rand::thread_rng();
+    for (entity, enemy, mut behavior, stimuli, mut agent, transform, squad_member) in enemy_query.iter_mut() {
+        behavior.state_time += fixed_time.period.as_secs_f32();
+        let position = transform.translation;
+
+        if behavior.patrol_points.is_empty() {
+            if let Some(navmesh) = &navmesh {
+                let home = behavior.home;
+                let reachable = navmesh.reachable_points(home, behavior.params.patrol_radius);
+                behavior.patrol_points = reachable.choose_multiple(&mut rng, 3).copied().collect();
+                behavior.patrol_points.insert(0, home);
+            }
+        }
+
+        let role = squad_member.map(|member| member.role);
+        let knows_target = stimuli.target_position.is_some();
+        let target = stimuli.target_position.unwrap_or(position);
+        let distance_to_target = position.distance(target);
+
+        //only bother searching for cover when the enemy is about to engage
+        let wants_cover = behavior.params.use_cover
+            && stimuli.can_see_target
+            && distance_to_target <= behavior.params.attack_range
+            && matches!(behavior.state, EnemyState::Alert | EnemyState::Chase);
+        behavior.cover_retry_timer -= fixed_time.period.as_secs_f32();
+        let threat_still = behavior
+            .failed_cover_threat
+            .is_some_and(|threat| threat.distance(target) <= COVER_RETRY_DISTANCE);
+        if wants_cover && behavior.cover_point.is_none() && (behavior.cover_retry_timer <= 0. || !threat_still) {
+            if let Some(navmesh) = &navmesh {
+                behavior.cover_point = find_cover(
+                    navmesh,
+                    &rapier_context,
+                    &hitbox_query,
+                    position,
+                    target,
+                    behavior.params.cover_search_radius,
+                );
+                //the search is a flood fill and a pile of raycasts, so a miss isn't repeated every tick
+                if behavior.cover_point.is_none() {
+                    behavior.cover_retry_timer = COVER_RETRY_TIME;
+                    behavior.failed_cover_threat = Some(target);
+                } else {
+                    behavior.failed_cover_threat = None;
+                }
+            }
+        }
+
+        let ctx = BehaviorContext {
+            alive: enemy.health > 0.,
+            can_see_target: stimuli.can_see_target,
+            knows_target,
+            distance_to_target,
+            health_fraction: enemy.health / behavior.max_health,
+            arrived: !agent.is_moving(),
+            has_patrol: behavior.patrol_points.len() > 1,
+            has_cover: behavior.cover_point.is_some(),
+        };
+        let new_state = next_state(behavior.state, behavior.state_time, &behavior.params, &ctx);
+        if new_state != behavior.state {
+            match new_state {
+                EnemyState::Patrol => {
+                    behavior.patrol_index = (behavior.patrol_index + 1) % behavior.patrol_points.len();
+                }
+                EnemyState::Retreat => {
+                    behavior.retreat_point = navmesh.as_ref().and_then(|navmesh| {
+                        navmesh
+                            .reachable_points(position, behavior.params.cover_search_radius * 2.)
+                            .into_iter()
+                            .max_by(|a, b| a.distance(target).total_cmp(&b.distance(target)))
+                    });
+                }
+                EnemyState::Chase if role == Some(SquadRole::Flank) => {
+                    behavior.flank_point = navmesh
+                        .as_ref()
+                        .and_then(|navmesh| find_flank_point(navmesh, position, target, entity.index() % 2 == 0));
+                }
+                EnemyState::Idle | EnemyState::Dead => {
+                    behavior.cover_point = None;
+                    behavior.retreat_point = None;
+                    behavior.flank_point = None;
+                }
+                _ => {}
+            }
+            behavior.state = new_state;
+            behavior.state_time = 0.;
+        }
+
+        match behavior.state {
+            EnemyState::Patrol => agent.set_destination(behavior.patrol_points[behavior.patrol_index]),
+            EnemyState::Chase => match (role, behavior.flank_point) {
+                (Some(SquadRole::Hold), _) => agent.stop(),
+                //head straight for the target once the flanking spot is reached
+                (Some(SquadRole::Flank), Some(flank_point)) if position.distance(flank_point) > 1.5 => {
+                    agent.set_destination(flank_point)
+                }
+                _ => agent.set_destination(target),
+            },
+            EnemyState::TakeCover => match behavior.cover_point {
+                Some(cover_point) => agent.set_destination(cover_point),
+                None => agent.stop(),
+            },
+            EnemyState::Retreat => match behavior.retreat_point {
+                Some(retreat_point) => agent.set_destination(retreat_point),
+                None => agent.stop(),
+            },
+            EnemyState::Idle | EnemyState::Alert | EnemyState::Attack | EnemyState::Dead => agent.stop(),
+        }
+    }
+}
+
+pub fn play_state_animations(
+    enemy_animations: Res<EnemyAnimations>,
+    mut animation_player_query: Query<&mut AnimationPlayer>,
+    enemy_query: Query<(&EnemyBehavior, &AnimationEntityLink)>,
+) {
+    for (behavior, animation_entity) in enemy_query.iter() {
+        //death animation is started when the enemy dies
+        if behavior.state == EnemyState::Dead {
+            continue;
+        }
+        if let Ok(mut player) = animation_player_query.get_mut(animation_entity.0) {
+            let (clip, speed) = state_animation(behavior.state);
+            player
+                .play(enemy_animations.0[clip].clone_weak())
+                .set_speed(speed)
+                .repeat();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use EnemyState::*;
+
+    //alive, at full health and with nothing to react to
+    fn calm() -> BehaviorContext {
+        BehaviorContext {
+            alive: true,
+            can_see_target: false,
+            knows_target: false,
+            distance_to_target: 100.,
+            health_fraction: 1.,
+            arrived: true,
+            has_patrol: false,
+            has_cover: false,
+        }
+    }
+
+    //sees its target from closer than the default attack range
+    fn engaged() -> BehaviorContext {
+        BehaviorContext {
+            can_see_target: true,
+            knows_target: true,
+            distance_to_target: 10.,
+            ..calm()
+        }
+    }
+
+    #[test]
+    fn idle_and_patrol_turn_alert_on_a_stimulus() {
+        let params = BehaviorParams::default();
+        let heard = BehaviorContext {
+            knows_target: true,
+            ..calm()
+        };
+        assert_eq!(next_state(Idle, 0., &params, &calm()), Idle);
+        assert_eq!(next_state(Idle, 0., &params, &heard), Alert);
+        assert_eq!(next_state(Patrol, 0., &params, &heard), Alert);
+    }
+
+    #[test]
+    fn idle_patrols_after_waiting_and_patrol_idles_on_arrival() {
+        let params = BehaviorParams::default();
+        let ctx = BehaviorContext {
+            has_patrol: true,
+            ..calm()
+        };
+        assert_eq!(next_state(Idle, params.idle_time * 0.5, &params, &ctx), Idle);
+        assert_eq!(next_state(Idle, params.idle_time, &params, &ctx), Patrol);
+        assert_eq!(next_state(Patrol, 1., &params, &ctx), Idle);
+        let walking = BehaviorContext { arrived: false, ..ctx };
+        assert_eq!(next_state(Patrol, 1., &params, &walking), Patrol);
+    }
+
+    #[test]
+    fn alert_waits_for_the_reaction_time_then_chases() {
+        let params = BehaviorParams::default();
+        let far = BehaviorContext {
+            distance_to_target: params.attack_range * 2.,
+            ..engaged()
+        };
+        assert_eq!(next_state(Alert, params.reaction_time * 0.5, &params, &far), Alert);
+        assert_eq!(next_state(Alert, params.reaction_time, &params, &far), Chase);
+    }
+
+    #[test]
+    fn chase_attacks_in_range() {
+        let params = BehaviorParams::default();
+        assert_eq!(next_state(Chase, 1., &params, &engaged()), Attack);
+        //out of range, or in range but unseen, keeps chasing
+        let far = BehaviorContext {
+            distance_to_target: params.attack_range * 2.,
+            ..engaged()
+        };
+        assert_eq!(next_state(Chase, 1., &params, &far), Chase);
+        let unseen = BehaviorContext {
+            can_see_target: false,
+            ..engaged()
+        };
+        assert_eq!(next_state(Chase, 1., &params, &unseen), Chase);
+        //and an attacker whose target runs off goes back to chasing
+        assert_eq!(next_state(Attack, 1., &params, &far), Chase);
+    }
+
+    #[test]
+    fn chase_takes_cover_in_range_when_there_is_some() {
+        let params = BehaviorParams::default();
+        let covered = BehaviorContext {
+            has_cover: true,
+            ..engaged()
+        };
+        assert_eq!(next_state(Chase, 1., &params, &covered), TakeCover);
+        let no_cover_wanted = BehaviorParams {
+            use_cover: false,
+            ..BehaviorParams::default()
+        };
+        assert_eq!(next_state(Chase, 1., &no_cover_wanted, &covered), Attack);
+        //stays in cover until it gets there, then fights from it
+        let moving = BehaviorContext { arrived: false, ..covered };
+        assert_eq!(next_state(TakeCover, 1., &params, &moving), TakeCover);
+        assert_eq!(next_state(TakeCover, 1., &params, &covered), Attack);
+    }
+
+    #[test]
+    fn low_health_retreats_until_the_target_is_lost() {
+        let params = BehaviorParams::default();
+        let hurt = BehaviorContext {
+            health_fraction: params.retreat_health,
+            ..engaged()
+        };
+        for state in [Alert, Chase, TakeCover, Attack] {
+            assert_eq!(next_state(state, 1., &params, &hurt), Retreat);
+        }
+        //healing up doesn't stop a retreat, only losing the target does
+        assert_eq!(next_state(Retreat, 1., &params, &engaged()), Retreat);
+        assert_eq!(next_state(Retreat, 1., &params, &calm()), Idle);
+    }
+
+    #[test]
+    fn losing_the_target_goes_back_to_idle() {
+        let params = BehaviorParams::default();
+        for state in [Alert, Chase, TakeCover, Attack] {
+            assert_eq!(next_state(state, 1., &params, &calm()), Idle);
+        }
+    }
+
+    #[test]
+    fn dead_is_terminal() {
+        let params = BehaviorParams::default();
+        let killed = BehaviorContext {
+            alive: false,
+            ..engaged()
+        };
+        for state in [Idle, Patrol, Alert, Chase, TakeCover, Attack, Retreat, Dead] {
+            assert_eq!(next_state(state, 1., &params, &killed), Dead);
+        }
+        //nothing it could be told brings it back
+        for ctx in [calm(), engaged()] {
+            assert_eq!(next_state(Dead, 100., &params, &ctx), Dead);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+use crate::{enemy_ai::BehaviorParams, team::Team};
+
+pub const ARCHETYPE_NAMES: [&str; 4] = ["rusher", "rifleman", "sniper", "heavy"];
+
+#[derive(Deserialize, Clone)]
+pub struct WeaponStats {
+    pub damage: f32,
+    //seconds between shots
+    pub cooldown: f32,
+    pub range: f32,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "0f6e2a51-8c0d-4b7e-9d6a-3c1f5e7b9a24"]
+pub struct EnemyArchetype {
+    //shown on the death screen as the killer's name
+    pub display_name: String,
+    pub model: String,
+    pub hitbox_profile: String,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    //set to the players' team for friendly bots
+    #[serde(default = "default_team")]
+    pub team: Team,
+    pub health: f32,
+    #[serde(default)]
+    pub armor: f32,
+    pub move_speed: f32,
+    //degrees per second
+    pub turn_rate: f32,
+    pub weapon: WeaponStats,
+    //chance each shot that would connect actually hits, 0 to 1
+    pub accuracy: f32,
+    #[serde(default)]
+    pub behavior: BehaviorParams,
+}
+
+fn default_scale() -> f32 {
+    2.5
+}
+
+fn default_team() -> Team {
+    Team::ENEMIES
+}
+
+#[derive(Default)]
+pub struct EnemyArchetypeLoader;
+
+impl AssetLoader for EnemyArchetypeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let archetype: EnemyArchetype = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(archetype));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["archetype.ron"]
+    }
+}
+
+//archetype handles by the name of their file in assets/archetypes
+#[derive(Resource, Default)]
+pub struct EnemyArchetypes(pub HashMap<String, Handle<EnemyArchetype>>);
+
+impl EnemyArchetypes {
+    pub fn load(asset_server: &AssetServer) -> Self {
+        EnemyArchetypes(
+            ARCHETYPE_NAMES
+                .iter()
+                .map(|name| (name.to_string(), asset_server.load(format!("archetypes/{}.archetype.ron", name))))
+                .collect(),
+        )
+    }
+
+    pub fn get<'a>(&self, name: &str, assets: &'a Assets<EnemyArchetype>) -> Option<&'a EnemyArchetype> {
+        self.0.get(name).and_then(|handle| assets.get(handle))
+    }
+}
@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    enemy::{can_see_entity, find_descendant_by_name, Enemy, EnemyHitboxFilter, MODEL_FORWARD},
+    enemy_ai::EnemyStimuli,
+    footsteps::Footstep,
+    player_health::PlayerHealth,
+    team::Team,
+};
+
+//roughly where the head bone sits above an enemy's feet
+pub const EYE_HEIGHT: f32 = 1.4;
+
+pub struct NoiseEvent {
+    pub position: Vec3,
+    //multiplier on each listener's hearing radius, 1 for a gunshot
+    pub loudness: f32,
+    //listeners ignore noises their own team makes
+    pub team: Option<Team>,
+}
+
+#[derive(Clone, Copy)]
+pub struct TargetMemory {
+    pub position: Vec3,
+    pub age: f32,
+}
+
+#[derive(Component)]
+pub struct Perception {
+    //full cone angle in degrees
+    pub fov: f32,
+    pub sight_range: f32,
+    //anything this close is noticed even outside the vision cone
+    pub proximity_radius: f32,
+    pub hearing_radius: f32,
+    pub memory_duration: f32,
+    pub head: Option<Entity>,
+    pub memory: Option<TargetMemory>,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Perception {
+            fov: 110.,
+            sight_range: 40.,
+            proximity_radius: 2.,
+            hearing_radius: 30.,
+            memory_duration: 8.,
+            head: None,
+            memory: None,
+        }
+    }
+}
+
+impl Perception {
+    pub fn in_vision_cone(&self, eye: Vec3, forward: Vec3, target: Vec3) -> bool {
+        let to_target = target - eye;
+        let distance = to_target.length();
+        if distance <= self.proximity_radius {
+            return true;
+        }
+        if distance > self.sight_range {
+            return false;
+        }
+        forward.angle_between(to_target).to_degrees() <= self.fov / 2.
+    }
+
+    pub fn can_hear(&self, ear: Vec3, noise: &NoiseEvent) -> bool {
+        ear.distance(noise.position) <= self.hearing_radius * noise.loudness
+    }
+
+    pub fn remember(&mut self, position: Vec3) {
+        self.memory = Some(TargetMemory { position, age: 0. });
+    }
+
+    //how sure the enemy still is about the remembered position, 1 when fresh
+    pub fn certainty(&self) -> f32 {
+        self.memory
+            .map_or(0., |memory| 1. - memory.age / self.memory_duration)
+    }
+
+    pub fn fade_memory(&mut self, delta: f32) {
+        if let Some(memory) = &mut self.memory {
+            memory.age += delta;
+            if memory.age >= self.memory_duration {
+                self.memory = None;
+            }
+        }
+    }
+}
+
+pub fn find_head_bones(
+    mut perception_query: Query<(Entity, &mut Perception)>,
+    children_query: Query<&Children>,
+    name_query: Query<&Name>,
+) {
+    for (entity, mut perception) in perception_query.iter_mut() {
+        if perception.head.is_none() {
+            perception.head = find_descendant_by_name(entity, "Head", &children_query, &name_query);
+        }
+    }
+}
+
+//something an enemy could pick as its target
+struct TargetCandidate {
+    entity: Entity,
+    position: Vec3,
+    team: Team,
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_perception(
+    fixed_time: Res<FixedTime>,
+    rapier_context: Res<RapierContext>,
+    mut noise_events: EventReader<NoiseEvent>,
+    player_query: Query<(Entity, &Transform, &PlayerHealth, &Team)>,
+    target_enemy_query: Query<(Entity, &Transform, &Enemy, &Team)>,
+    mut enemy_query: Query<(Entity, &Transform, &Team, &mut Perception, &mut EnemyStimuli), With<Enemy>>,
+    bone_query: Query<&GlobalTransform>,
+    hitbox_query: Query<(), EnemyHitboxFilter>,
+) {
+    let noises: Vec<&NoiseEvent> = noise_events.iter().collect();
+    let mut candidates: Vec<TargetCandidate> = player_query
+        .iter()
+        .filter(|(_, _, player_health, _)| !player_health.dead)
+        .map(|(entity, transform, _, team)| TargetCandidate {
+            entity,
+            position: transform.translation,
+            team: *team,
+        })
+        .collect();
+    candidates.extend(
+        target_enemy_query
+            .iter()
+            .filter(|(_, _, enemy, _)| enemy.health > 0.)
+            .map(|(entity, transform, _, team)| TargetCandidate {
+                entity,
+                position: transform.translation + Vec3::Y * EYE_HEIGHT,
+                team: *team,
+            }),
+    );
+
+    for (entity, transform, team, mut perception, mut stimuli) in enemy_query.iter_mut() {
+        perception.fade_memory(fixed_time.period.as_secs_f32());
+        //forget a target that has died
+        if stimuli.target.is_some_and(|target| !candidates.iter().any(|candidate| candidate.entity == target)) {
+            perception.memory = None;
+            stimuli.target = None;
+        }
+        let eye = perception
+            .head
+            .and_then(|head| bone_query.get(head).ok())
+            .map_or(transform.translation + Vec3::Y * EYE_HEIGHT, |head| head.translation());
+        let forward = transform.rotation * MODEL_FORWARD;
+
+        let seen = candidates
+            .iter()
+            .filter(|candidate| candidate.entity != entity && team.is_hostile_to(candidate.team))
+            .filter(|candidate| {
+                perception.in_vision_cone(eye, forward, candidate.position)
+                    && can_see_entity(&rapier_context, eye, candidate.position, candidate.entity, &hitbox_query)
+            })
+            .min_by(|a, b| a.position.distance(eye).total_cmp(&b.position.distance(eye)));
+        let can_see = seen.is_some();
+        if let Some(seen) = seen {
+            perception.remember(seen.position);
+            stimuli.target = Some(seen.entity);
+        } else if let Some(noise) = noises.iter().rev().find(|noise| {
+            noise.team.is_none_or(|noise_team| team.is_hostile_to(noise_team)) && perception.can_hear(eye, noise)
+        }) {
+            perception.remember(noise.position);
+        }
+
+        stimuli.can_see_target = can_see;
+        stimuli.target_position = perception.memory.map(|memory| memory.position);
+        stimuli.certainty = perception.certainty();
+    }
+}
+
+//footsteps are heard the same way as any other noise, louder on loud surfaces
+pub fn hear_footsteps(
+    mut footstep_events: EventReader<Footstep>,
+    mut noise_events: EventWriter<NoiseEvent>,
+) {
+    for footstep in footstep_events.iter() {
+        noise_events.send(NoiseEvent {
+            position: footstep.position,
+            loudness: footstep.loudness * footstep.surface.loudness(),
+            team: footstep.team,
+        });
+    }
+}
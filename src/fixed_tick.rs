@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use bevy::{ecs::event::Event, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+use crate::{input_map::ActionState, noclip::Noclip};
+
+//each fixed tick runs begin, gameplay, rapier's physics sets and then end
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[system_set(base)]
+pub enum TickSet {
+    Begin,
+    Gameplay,
+    End,
+}
+
+//how many gameplay and physics ticks run per second, independent of the frame rate
+#[derive(Resource)]
+pub struct TickRate {
+    pub hz: f32,
+}
+
+impl Default for TickRate {
+    fn default() -> Self {
+        TickRate { hz: 64. }
+    }
+}
+
+//the transform as of the last two physics steps, frames are drawn somewhere in between
+#[derive(Component)]
+pub struct InterpolatedTransform {
+    pub previous_translation: Vec3,
+    pub translation: Vec3,
+    pub previous_rotation: Quat,
+    pub rotation: Quat,
+    //the player's rotation follows the mouse every frame, so only the translation is theirs to interpolate
+    pub interpolate_rotation: bool,
+}
+
+impl InterpolatedTransform {
+    pub fn new(transform: &Transform, interpolate_rotation: bool) -> Self {
+        InterpolatedTransform {
+            previous_translation: transform.translation,
+            translation: transform.translation,
+            previous_rotation: transform.rotation,
+            rotation: transform.rotation,
+            interpolate_rotation,
+        }
+    }
+}
+
+//moves further than this in one tick are teleports and snap instead of sliding across the map
+const TELEPORT_DISTANCE: f32 = 2.;
+
+//puts the tick schedule together: gameplay between begin and end with rapier stepping once per tick after it
+pub struct FixedTickPlugin;
+
+impl Plugin for FixedTickPlugin {
+    fn build(&self, app: &mut App) {
+        app.edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+            schedule.set_default_base_set(TickSet::Gameplay).configure_sets(
+                (
+                    TickSet::Begin,
+                    TickSet::Gameplay,
+                    PhysicsSet::SyncBackend,
+                    PhysicsSet::SyncBackendFlush,
+                    PhysicsSet::StepSimulation,
+                    PhysicsSet::Writeback,
+                    TickSet::End,
+                )
+                    .chain(),
+            );
+        });
+        for set in [
+            PhysicsSet::SyncBackend,
+            PhysicsSet::SyncBackendFlush,
+            PhysicsSet::StepSimulation,
+            PhysicsSet::Writeback,
+        ] {
+            app.add_systems(
+                RapierPhysicsPlugin::<NoUserData>::get_systems(set.clone())
+                    .in_base_set(set)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+        }
+        app.init_resource::<TickRate>()
+            .add_system(apply_tick_rate.in_base_set(CoreSet::PreUpdate))
+            .add_system(
+                restore_tick_transforms
+                    .in_base_set(TickSet::Begin)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                record_tick_transforms
+                    .in_base_set(TickSet::End)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(
+                consume_tick_input
+                    .in_base_set(TickSet::End)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(interpolate_transforms.in_base_set(CoreSet::Update));
+    }
+}
+
+//for events that are both sent and read on the tick, they are cleared per tick so a reader that runs earlier in the tick still sees them next tick
+pub trait AddTickEvent {
+    fn add_tick_event<T: Event>(&mut self) -> &mut Self;
+}
+
+impl AddTickEvent for App {
+    fn add_tick_event<T: Event>(&mut self) -> &mut Self {
+        if !self.world.contains_resource::<Events<T>>() {
+            self.init_resource::<Events<T>>().add_system(
+                Events::<T>::update_system
+                    .in_base_set(TickSet::Begin)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+        }
+        self
+    }
+}
+
+pub fn apply_tick_rate(
+    tick_rate: Res<TickRate>,
+    mut fixed_time: ResMut<FixedTime>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    if !tick_rate.is_changed() {
+        return;
+    }
+    let dt = 1. / tick_rate.hz.max(1.);
+    fixed_time.period = Duration::from_secs_f32(dt);
+    rapier_config.timestep_mode = TimestepMode::Fixed { dt, substeps: 1 };
+}
+
+//undoes the last frame's interpolation so gameplay and physics carry on from where the simulation really is
+pub fn restore_tick_transforms(mut query: Query<(&mut Transform, &InterpolatedTransform)>) {
+    for (mut transform, interpolated) in query.iter_mut() {
+        transform.translation = interpolated.translation;
+        if interpolated.interpolate_rotation {
+            transform.rotation = interpolated.rotation;
+        }
+    }
+}
+
+pub fn record_tick_transforms(mut query: Query<(&Transform, &mut InterpolatedTransform, Option<&Noclip>)>) {
+    for (transform, mut interpolated, noclip) in query.iter_mut() {
+        interpolated.previous_translation = interpolated.translation;
+        interpolated.previous_rotation = interpolated.rotation;
+        interpolated.translation = transform.translation;
+        interpolated.rotation = transform.rotation;
+        //fast noclip flight covers more than the teleport distance every tick and would stutter if it snapped
+        if noclip.is_none() && interpolated.previous_translation.distance(interpolated.translation) > TELEPORT_DISTANCE {
+            interpolated.previous_translation = interpolated.translation;
+            interpolated.previous_rotation = interpolated.rotation;
+        }
+    }
+}
+
+//runs every frame after the ticks, blending by how far the frame got into the next tick
+pub fn interpolate_transforms(fixed_time: Res<FixedTime>, mut query: Query<(&mut Transform, &InterpolatedTransform)>) {
+    let alpha = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.);
+    for (mut transform, interpolated) in query.iter_mut() {
+        transform.translation = interpolated.previous_translation.lerp(interpolated.translation, alpha);
+        if interpolated.interpolate_rotation {
+            transform.rotation = interpolated.previous_rotation.slerp(interpolated.rotation, alpha);
+        }
+    }
+}
+
+//presses and releases are kept until a tick has seen them, then dropped
+pub fn consume_tick_input(mut action_state: ResMut<ActionState>) {
+    action_state.clear_tick_input();
+}
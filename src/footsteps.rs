@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::{
+    crouch::Crouch,
+    fps_movement::{FPSMovement, GroundState},
+    player_health::PlayerHealth,
+    team::Team,
+    traversal::Traversal,
+};
+
+//what a collider sounds like to walk on, colliders without one count as Default
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SurfaceType {
+    #[default]
+    Default,
+    Sand,
+    Concrete,
+    Metal,
+    Wood,
+}
+
+impl SurfaceType {
+    pub fn loudness(&self) -> f32 {
+        match self {
+            SurfaceType::Default => 1.,
+            SurfaceType::Sand => 0.7,
+            SurfaceType::Concrete => 1.,
+            SurfaceType::Metal => 1.5,
+            SurfaceType::Wood => 1.2,
+        }
+    }
+}
+
+pub struct Footstep {
+    pub entity: Entity,
+    pub position: Vec3,
+    pub surface: SurfaceType,
+    //0 to 1 before the surface is taken into account
+    pub loudness: f32,
+    pub team: Option<Team>,
+}
+
+#[derive(Component)]
+pub struct Footsteps {
+    //distance walked between two steps
+    pub stride: f32,
+    pub walked: f32,
+    pub loudness: f32,
+    //applied while fully crouched
+    pub crouch_multiplier: f32,
+    //below this fraction of full speed steps drop off to the slow multiplier
+    pub slow_fraction: f32,
+    pub slow_multiplier: f32,
+}
+
+impl Default for Footsteps {
+    fn default() -> Self {
+        Footsteps {
+            stride: 1.6,
+            walked: 0.,
+            loudness: 0.25,
+            crouch_multiplier: 0.3,
+            slow_fraction: 0.6,
+            slow_multiplier: 0.4,
+        }
+    }
+}
+
+//a step goes off every stride walked on the ground
+#[allow(clippy::type_complexity)]
+pub fn emit_footsteps(
+    fixed_time: Res<FixedTime>,
+    surface_query: Query<&SurfaceType>,
+    mut player_query: Query<(
+        Entity,
+        &Transform,
+        &Velocity,
+        &FPSMovement,
+        &GroundState,
+        &mut Footsteps,
+        &PlayerHealth,
+        Option<&Team>,
+        Option<&Crouch>,
+        Option<&Traversal>,
+    )>,
+    mut footstep_events: EventWriter<Footstep>,
+) {
+    for (entity, transform, velocity, movement, ground, mut footsteps, player_health, team, crouch, traversal) in
+        player_query.iter_mut()
+    {
+        let speed = Vec2::new(velocity.linvel.x, velocity.linvel.z).length();
+        let traversing = traversal.is_some_and(|traversal| !traversal.is_free());
+        if player_health.dead || !ground.grounded || traversing {
+            //the first step after landing comes sooner
+            footsteps.walked = footsteps.stride * 0.5;
+            continue;
+        }
+        footsteps.walked += speed * fixed_time.period.as_secs_f32();
+        if footsteps.walked < footsteps.stride {
+            continue;
+        }
+        footsteps.walked = 0.;
+
+        let speed_fraction = (speed / movement.speed).min(1.);
+        let mut loudness = footsteps.loudness;
+        if speed_fraction < footsteps.slow_fraction {
+            loudness *= footsteps.slow_multiplier;
+        }
+        if let Some(crouch) = crouch {
+            loudness *= 1. + (footsteps.crouch_multiplier - 1.) * crouch.amount;
+        }
+        let surface = ground
+            .entity
+            .and_then(|ground_entity| surface_query.get(ground_entity).ok())
+            .copied()
+            .unwrap_or_default();
+        footstep_events.send(Footstep {
+            entity,
+            position: transform.translation,
+            surface,
+            loudness,
+            team: team.copied(),
+        });
+    }
+}
@@ -1,52 +1,126 @@
 use bevy::{input::mouse::MouseMotion, prelude::*};
 
-use crate::{lock_cursor::CursorLockState, vector_operations::move_towards};
+use crate::{
+    camera_effects::CameraEffects,
+    gamepad::{AimAssist, GamepadConfig},
+    gun_control::GunController,
+    input_map::ActionState,
+    lock_cursor::CursorLockState,
+    player_health::PlayerHealth,
+    sensitivity::SensitivitySettings,
+    spectator::Spectator,
+    vector_operations::move_towards,
+};
 
 #[derive(Component)]
 pub struct FPSCamera {
     pub speed: f32,
-    pub sensitivity: f32,
     pub rotate_lock: f32,
+    //vertical, radians, at the hip and fully aimed down the sights
+    pub fov: f32,
+    pub ads_fov: f32,
 
     pub rotation: Vec3,
     pub recoil_shake: Vec3,
     pub camera_shake_readjustment_factor: f32,
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn move_camera(
     cursor_lock_state: Res<CursorLockState>,
     mut motion_evr: EventReader<MouseMotion>,
     time: Res<Time>,
-    mut camera_query: Query<(&mut Transform, &mut FPSCamera)>,
+    action_state: Res<ActionState>,
+    gamepad_config: Res<GamepadConfig>,
+    aim_assist: Res<AimAssist>,
+    spectator: Res<Spectator>,
+    sensitivity: Res<SensitivitySettings>,
+    gun_query: Query<&GunController>,
+    mut camera_query: Query<(
+        &mut Transform,
+        &mut FPSCamera,
+        &Projection,
+        Option<&CameraEffects>,
+        Option<&PlayerHealth>,
+    )>,
 ) {
-    if cursor_lock_state.state {
-        for (mut transform, mut camera) in camera_query.iter_mut() {
-            for ev in motion_evr.iter() {
-                camera.rotation.y -= ev.delta.x * camera.sensitivity;
-                camera.rotation.x -= ev.delta.y * camera.sensitivity;
-
-                camera.rotation.x =
-                    f32::clamp(camera.rotation.x, -camera.rotate_lock, camera.rotate_lock);
-
-                //transform.rotation += Quat::from_axis_angle(Vec3::new(0., 1., 0.), angle);
-                //println!("{}",x_quat);
-            }
-            camera.recoil_shake = move_towards(
-                camera.recoil_shake,
-                Vec3::ZERO,
-                time.delta_seconds() * camera.camera_shake_readjustment_factor,
-            );
-            let x_quat = Quat::from_axis_angle(
-                Vec3::new(0., 1., 0.),
-                camera.rotation.y - camera.recoil_shake.x,
-            );
-
-            let y_quat = Quat::from_axis_angle(
-                Vec3::new(1., 0., 0.),
-                camera.rotation.x + camera.recoil_shake.y,
-            );
-
-            transform.rotation = x_quat * y_quat;
+    //the spectator camera has the mouse
+    if spectator.active {
+        motion_evr.clear();
+        return;
+    }
+    //the stick works without the cursor being locked, so a controller never has to click into the window
+    let stick_active = action_state.look != Vec2::ZERO;
+    for (mut transform, mut camera, projection, effects, player_health) in camera_query.iter_mut() {
+        //the view stays where the player fell until they respawn
+        let dead = player_health.is_some_and(|player_health| player_health.dead);
+        if dead {
+            motion_evr.clear();
+        } else if cursor_lock_state.state {
+            let counts: Vec2 = motion_evr.iter().map(|ev| ev.delta).sum();
+            let ads_amount = gun_query.iter().map(|gun_controller| gun_controller.ads_amount).fold(0., f32::max);
+            let zoom_ratio = match projection {
+                Projection::Perspective(perspective) => {
+                    sensitivity.zoom_ratio(camera.fov, perspective.fov, perspective.aspect_ratio, ads_amount)
+                }
+                Projection::Orthographic(_) => 1.,
+            };
+            //accel looks at the whole frame's movement, so it doesn't depend on how many events the mouse sent
+            let counts_per_ms = counts.length() / (time.delta_seconds() * 1000.).max(0.1);
+            let turn = counts * sensitivity.radians_per_count() * zoom_ratio * sensitivity.accel_gain(counts_per_ms);
+            camera.rotation.y -= turn.x;
+            camera.rotation.x -= turn.y;
+        }
+        if stick_active && !dead {
+            let invert = if gamepad_config.invert_y { -1. } else { 1. };
+            let look = action_state.look * aim_assist.slowdown * time.delta_seconds();
+            camera.rotation.y -= look.x * gamepad_config.yaw_speed;
+            camera.rotation.x += look.y * gamepad_config.pitch_speed * invert;
+        }
+        if !dead {
+            camera.rotation.y += aim_assist.pull.x;
+            camera.rotation.x += aim_assist.pull.y;
+        }
+        camera.rotation.x =
+            f32::clamp(camera.rotation.x, -camera.rotate_lock, camera.rotate_lock);
+
+        camera.recoil_shake = move_towards(
+            camera.recoil_shake,
+            Vec3::ZERO,
+            time.delta_seconds() * camera.camera_shake_readjustment_factor,
+        );
+        //shake, punch and bob from the effects stack, none of them touch where the player is really aiming
+        let (effect_angles, effect_offset) = effects.map_or((Vec3::ZERO, Vec3::ZERO), |effects| (effects.angles, effects.offset));
+        let x_quat = Quat::from_axis_angle(
+            Vec3::new(0., 1., 0.),
+            camera.rotation.y - camera.recoil_shake.x + effect_angles.y,
+        );
+
+        let y_quat = Quat::from_axis_angle(
+            Vec3::new(1., 0., 0.),
+            camera.rotation.x + camera.recoil_shake.y + effect_angles.x,
+        );
+
+        //roll, used by leaning and the head bob
+        let z_quat = Quat::from_axis_angle(Vec3::new(0., 0., 1.), camera.rotation.z + effect_angles.z);
+
+        let rotation = x_quat * y_quat * z_quat;
+        transform.rotation = rotation;
+        //the translation is put back from the last tick before physics runs, so the offset never builds up
+        transform.translation += rotation * effect_offset;
+    }
+}
+
+//narrows the view as the sights come up, and widens it by the sprint kick
+pub fn apply_fov(
+    gun_query: Query<&GunController>,
+    mut camera_query: Query<(&FPSCamera, &mut Projection, Option<&CameraEffects>)>,
+) {
+    let ads_amount = gun_query.iter().map(|gun_controller| gun_controller.ads_amount).fold(0., f32::max);
+    for (camera, mut projection, effects) in camera_query.iter_mut() {
+        if let Projection::Perspective(perspective) = &mut *projection {
+            let kick = effects.map_or(0., |effects| effects.fov_offset);
+            perspective.fov = camera.fov + (camera.ads_fov - camera.fov) * ads_amount + kick;
         }
     }
 }
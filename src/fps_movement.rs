@@ -1,49 +1,238 @@
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-use crate::{fps_camera::FPSCamera, MapStatus};
+use crate::{character_controller::PLAYER_HALF_EXTENTS, console::{Console, ConsoleCommand}, crouch::Crouch, enemy::EnemyHitboxFilter, fps_camera::FPSCamera, input_map::{Action, ActionState}, noclip::Noclip, player_health::PlayerHealth, slide::{slide_velocity, Slide}, sprint::Sprint, traversal::Traversal, MapStatus};
 #[derive(Component)]
 pub struct FPSMovement {
     pub acceleration: f32,
 
     pub speed: f32,
+
+    pub model: MovementModel,
 }
 
-pub fn player_movement(
-    map_status : Res<MapStatus>,
-    time: Res<Time>,
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MovementModel {
+    //damping on the ground, a flat air modifier and a hard cap on horizontal speed
+    Classic,
+    //friction plus sv_accelerate/sv_airaccelerate style acceleration, no speed cap so air strafing works
+    Source(SourceMovementProfile),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SourceMovementProfile {
+    //sv_friction
+    pub friction: f32,
+    //sv_stopspeed, speeds below this lose speed as if they were moving this fast
+    pub stop_speed: f32,
+    //sv_accelerate
+    pub accelerate: f32,
+    //sv_airaccelerate
+    pub air_accelerate: f32,
+    //the wish speed air acceleration can add up to along the wish direction, 30 units in source
+    pub max_air_wish_speed: f32,
+    pub jump_speed: f32,
+    //jumps as soon as the player lands while jump is held
+    pub auto_bunnyhop: bool,
+}
+
+impl SourceMovementProfile {
+    //roughly counter-strike's defaults converted from units to metres
+    pub fn source() -> Self {
+        SourceMovementProfile {
+            friction: 5.2,
+            stop_speed: 1.9,
+            accelerate: 5.5,
+            air_accelerate: 12.,
+            max_air_wish_speed: 0.57,
+            jump_speed: 4.,
+            auto_bunnyhop: false,
+        }
+    }
+
+    //looser friction and much stronger air control
+    pub fn quake() -> Self {
+        SourceMovementProfile {
+            friction: 4.,
+            stop_speed: 1.9,
+            accelerate: 10.,
+            air_accelerate: 100.,
+            max_air_wish_speed: 0.57,
+            jump_speed: 4.,
+            auto_bunnyhop: true,
+        }
+    }
+}
+
+impl MovementModel {
+    //classic, then the source profile, then the quake one
+    pub fn next(&self) -> MovementModel {
+        match self {
+            MovementModel::Classic => MovementModel::Source(SourceMovementProfile::source()),
+            MovementModel::Source(profile) if *profile == SourceMovementProfile::source() => {
+                MovementModel::Source(SourceMovementProfile::quake())
+            }
+            MovementModel::Source(_) => MovementModel::Classic,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MovementModel::Classic => "classic",
+            MovementModel::Source(profile) if *profile == SourceMovementProfile::source() => "source",
+            MovementModel::Source(profile) if *profile == SourceMovementProfile::quake() => "quake",
+            MovementModel::Source(_) => "custom",
+        }
+    }
+}
+
+//cycles the player through the movement models so they can be compared in game, or picks one from the console
+pub fn toggle_movement_model(
+    action_state: Res<ActionState>,
+    mut command_events: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    mut movement_query: Query<&mut FPSMovement>,
+) {
+    let next = action_state.just_pressed(Action::MovementModel);
+    let mut model = None;
+    for command in command_events.iter() {
+        if command.name != "movement" {
+            continue;
+        }
+        match command.args.first().map(String::as_str) {
+            Some("classic") => model = Some(MovementModel::Classic),
+            Some("source") => model = Some(MovementModel::Source(SourceMovementProfile::source())),
+            Some("quake") => model = Some(MovementModel::Source(SourceMovementProfile::quake())),
+            _ => console.print("usage: movement classic|source|quake"),
+        }
+    }
+    if !next && model.is_none() {
+        return;
+    }
+    for mut movement in movement_query.iter_mut() {
+        movement.model = model.unwrap_or_else(|| movement.model.next());
+        console.print(format!("movement {}", movement.model.label()));
+    }
+}
+
+#[derive(Component)]
+pub struct SpeedText;
+
+//what the player is standing on, refreshed every frame before movement runs
+#[derive(Component, Default)]
+pub struct GroundState {
+    pub grounded: bool,
+    pub normal: Vec3,
+    pub entity: Option<Entity>,
+}
+
+//extra reach below the feet that still counts as standing
+const GROUND_PROBE_MARGIN: f32 = 0.1;
+
+//casts straight down from the centre of the collider to just past the feet
+pub fn detect_ground(
     rapier_context: Res<RapierContext>,
-    mut movement_query: Query<(
+    hitbox_query: Query<(), EnemyHitboxFilter>,
+    mut player_query: Query<(
         &Transform,
-        &mut Damping,
-        &mut FPSCamera,
-        &mut FPSMovement,
-        &mut Velocity,
+        &mut GroundState,
+        Option<&Crouch>,
+        Option<&KinematicCharacterControllerOutput>,
     )>,
-    key: Res<Input<KeyCode>>,
 ) {
-    for (transform, mut damping, camera, movement, mut velocity) in movement_query.iter_mut() {
+    let not_hitbox = |collider: Entity| !hitbox_query.contains(collider);
+    let filter = QueryFilter::only_fixed().exclude_sensors().predicate(&not_hitbox);
+    for (transform, mut ground, crouch, controller_output) in player_query.iter_mut() {
+        let half_height = crouch.map_or(PLAYER_HALF_EXTENTS.y, |crouch| crouch.half_height());
+        let hit = rapier_context.cast_ray_and_get_normal(
+            transform.translation,
+            Vec3::NEG_Y,
+            half_height + GROUND_PROBE_MARGIN,
+            true,
+            filter,
+        );
+        ground.entity = hit.map(|(entity, _)| entity);
+        ground.normal = hit.map_or(Vec3::Y, |(_, intersection)| intersection.normal);
+        //the character controller knows about ground the single ray misses, like the edge of a step
+        ground.grounded = hit.is_some() || controller_output.is_some_and(|output| output.grounded);
+    }
+}
+
+//pm_friction: bleeds off horizontal speed while on the ground
+fn apply_friction(velocity: &mut Vec2, profile: &SourceMovementProfile, delta: f32) {
+    let speed = velocity.length();
+    if speed < 0.01 {
+        *velocity = Vec2::ZERO;
+        return;
+    }
+    let control = speed.max(profile.stop_speed);
+    let new_speed = (speed - control * profile.friction * delta).max(0.);
+    *velocity *= new_speed / speed;
+}
+
+//pm_accelerate and pm_airaccelerate: only adds speed along the wish direction up to wish_cap
+fn accelerate(velocity: &mut Vec2, wish_direction: Vec2, wish_speed: f32, wish_cap: f32, acceleration: f32, delta: f32) {
+    let current_speed = velocity.dot(wish_direction);
+    let add_speed = wish_cap - current_speed;
+    if add_speed <= 0. {
+        return;
+    }
+    let accel_speed = (acceleration * delta * wish_speed).min(add_speed);
+    *velocity += wish_direction * accel_speed;
+}
+
+#[allow(clippy::type_complexity)]
+pub fn player_movement(
+    map_status : Res<MapStatus>,
+    fixed_time: Res<FixedTime>,
+    mut movement_query: Query<
+        (
+            &GroundState,
+            &mut Damping,
+            &mut FPSCamera,
+            &mut FPSMovement,
+            &mut Velocity,
+            &PlayerHealth,
+            Option<&Crouch>,
+            Option<&Sprint>,
+            Option<&Traversal>,
+            Option<&Slide>,
+        ),
+        Without<Noclip>,
+    >,
+    action_state: Res<ActionState>,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    for (ground, mut damping, camera, movement, mut velocity, player_health, crouch, sprint, traversal, slide) in
+        movement_query.iter_mut()
+    {
+        if player_health.dead {
+            continue;
+        }
+
+        //mantles drive the velocity themselves, ladders swap walking for climbing
+        if let Some(traversal) = traversal.filter(|traversal| !traversal.is_free()) {
+            damping.linear_damping = 0.;
+            if traversal.is_climbing() {
+                let climb = action_state.movement.y;
+                let strafe = action_state.movement.x;
+                let right = Vec3::new(f32::cos(camera.rotation.y), 0., -f32::sin(camera.rotation.y));
+                velocity.linvel = Vec3::Y * climb * traversal.climb_speed
+                    + right * strafe * traversal.climb_speed * 0.5;
+            }
+            continue;
+        }
         let mut direction = Vec2::new(0., 0.);
 
         let mut air_modifier = 1.0;
 
-        let mut grounded = false;
-        let ray = Ray {
-            origin: transform.translation - 0.5,
-            direction: Vec3::new(0., -1., 0.),
-        };
-
-        // Then cast the ray.
-        let hit = rapier_context.cast_ray(
-            ray.origin,
-            ray.direction,
-            1.,
-            true,
-            QueryFilter::only_fixed(),
-        );
+        let mut grounded = ground.grounded;
 
-        if let Some((_entity, _toi)) = hit {
-            grounded = true;
+        //a slide keeps its momentum instead of being capped, only friction and slopes change it
+        if let Some(slide) = slide.filter(|slide| slide.active) {
+            damping.linear_damping = 0.;
+            velocity.linvel = slide_velocity(slide, ground, velocity.linvel, delta);
+            continue;
         }
 
         if grounded == false {
@@ -52,46 +241,77 @@ pub fn player_movement(
         } else {
             damping.linear_damping = 8.;
         }
-        if key.pressed(KeyCode::W) {
-            direction.y += -f32::cos(camera.rotation.y);
-            direction.x += -f32::sin(camera.rotation.y);
-        } else if key.pressed(KeyCode::S) {
-            direction.y += f32::cos(camera.rotation.y);
-            direction.x += f32::sin(camera.rotation.y);
-        }
-        if key.pressed(KeyCode::D) {
-            direction.y += f32::cos(camera.rotation.y + f32::to_radians(90.));
-            direction.x += f32::sin(camera.rotation.y + f32::to_radians(90.));
-        } else if key.pressed(KeyCode::A) {
-            direction.y += f32::cos(camera.rotation.y - f32::to_radians(90.));
-            direction.x += f32::sin(camera.rotation.y - f32::to_radians(90.));
-        }
-
-        if key.just_pressed(KeyCode::Space) {
-            if grounded {
-                velocity.linvel.y = 4.;
-            }
-        }
+        //opposing keys cancel out rather than the first one checked winning
+        let forward = action_state.movement.y;
+        let right = action_state.movement.x;
+        direction.y += -f32::cos(camera.rotation.y) * forward;
+        direction.x += -f32::sin(camera.rotation.y) * forward;
+        direction.y += f32::cos(camera.rotation.y + f32::to_radians(90.)) * right;
+        direction.x += f32::sin(camera.rotation.y + f32::to_radians(90.)) * right;
 
+        //a half pushed stick walks at half speed, keys are always all the way
+        let input_scale = action_state.movement.length();
         if direction.length() != 0. {
             direction = direction.normalize();
         }
 
-        velocity.linvel.x +=
-            direction.x * movement.acceleration * time.delta_seconds() * air_modifier;
-        velocity.linvel.z +=
-            direction.y * movement.acceleration * time.delta_seconds() * air_modifier;
+        let speed = movement.speed
+            * if input_scale > 0. { input_scale } else { 1. }
+            * crouch.map_or(1., |crouch| crouch.speed_scale())
+            * sprint.map_or(1., |sprint| sprint.speed_scale());
 
-        let net_velocity = Vec2::new(velocity.linvel.x, velocity.linvel.z).length();
-        let multiplier;
-        if net_velocity > movement.speed {
-            multiplier = movement.speed / net_velocity;
-        } else {
-            multiplier = 1.0;
-        }
+        match movement.model {
+            MovementModel::Classic => {
+                if action_state.pressed_this_tick(Action::Jump) {
+                    if grounded {
+                        velocity.linvel.y = 4.;
+                    }
+                }
+
+                velocity.linvel.x +=
+                    direction.x * movement.acceleration * delta * air_modifier;
+                velocity.linvel.z +=
+                    direction.y * movement.acceleration * delta * air_modifier;
+
+                let net_velocity = Vec2::new(velocity.linvel.x, velocity.linvel.z).length();
+                let multiplier;
+                if net_velocity > speed {
+                    multiplier = speed / net_velocity;
+                } else {
+                    multiplier = 1.0;
+                }
+
+                velocity.linvel.x *= multiplier;
+                velocity.linvel.z *= multiplier;
+            }
+            MovementModel::Source(profile) => {
+                //friction is done by hand so bunnyhops keep their speed
+                damping.linear_damping = 0.;
+                let mut horizontal = Vec2::new(velocity.linvel.x, velocity.linvel.z);
 
-        velocity.linvel.x *= multiplier;
-        velocity.linvel.z *= multiplier;
+                let jump = if profile.auto_bunnyhop {
+                    action_state.pressed(Action::Jump)
+                } else {
+                    action_state.pressed_this_tick(Action::Jump)
+                };
+                //a jump skips the landing frame's friction entirely
+                if grounded && jump {
+                    velocity.linvel.y = profile.jump_speed;
+                    grounded = false;
+                }
+
+                if grounded {
+                    apply_friction(&mut horizontal, &profile, delta);
+                    accelerate(&mut horizontal, direction, speed, speed, profile.accelerate, delta);
+                } else if direction != Vec2::ZERO {
+                    let wish_cap = speed.min(profile.max_air_wish_speed);
+                    accelerate(&mut horizontal, direction, speed, wish_cap, profile.air_accelerate, delta);
+                }
+
+                velocity.linvel.x = horizontal.x;
+                velocity.linvel.z = horizontal.y;
+            }
+        }
 
         if map_status.loaded == false
         {
@@ -101,3 +321,24 @@ pub fn player_movement(
         //velocity.linvel.z = f32::clamp(velocity.linvel.z,-movement.speed,movement.speed);
     }
 }
+
+pub fn update_speed_text(
+    action_state: Res<ActionState>,
+    movement_query: Query<&Velocity, With<FPSMovement>>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<SpeedText>>,
+) {
+    let Ok(velocity) = movement_query.get_single() else {
+        return;
+    };
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        if action_state.just_pressed(Action::DebugHud) {
+            *visibility = if *visibility == Visibility::Hidden {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+        }
+        let horizontal = Vec2::new(velocity.linvel.x, velocity.linvel.z).length();
+        text.sections[0].value = format!("{:.2} m/s", horizontal);
+    }
+}
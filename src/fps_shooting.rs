@@ -6,9 +6,15 @@ use bevy_rapier3d::prelude::*;
 use rand::Rng;
 
 use crate::bullet_tracer::BulletTracer;
-use crate::enemy::{HeadCollider, BodyCollider, LegCollider, Enemy};
+use crate::enemy::Enemy;
+use crate::enemy_perception::NoiseEvent;
 use crate::fps_camera::FPSCamera;
 use crate::gun_control::{translate_gun_position, GunController};
+use crate::hitbox::Hitbox;
+use crate::input_map::{Action, ActionState};
+use crate::player_health::PlayerHealth;
+use crate::ragdoll::EnemyKilledEvent;
+use crate::team::{FriendlyFire, Team};
 use crate::rotation_operations::quaternion_look_rotation;
 use crate::score_ui::ScoreText;
 use crate::vector_operations::move_towards;
@@ -49,32 +55,53 @@ pub fn update_bullet_params(
         (&mut GunController, &mut Transform, &AnimationEntityLink),
         (Without<FPSCamera>, Without<ShootableTarget>),
     >,
-    time: Res<Time>,
-    buttons: Res<Input<MouseButton>>,
+    fixed_time: Res<FixedTime>,
+    action_state: Res<ActionState>,
+    player_query: Query<(&PlayerHealth, &Transform, &Team), Without<GunController>>,
+    mut noise_events: EventWriter<NoiseEvent>,
 ) {
+    let Ok((player_health, player_transform, player_team)) = player_query.get_single() else {
+        return;
+    };
+    let player_dead = player_health.dead;
+    let delta = fixed_time.period.as_secs_f32();
     for (mut gun_controller, _gun_transform, _animation_entity) in gun_query.iter_mut() {
         if gun_controller.time_since_last_shot >= gun_controller.recoil_reset_time {
             gun_controller.spray_index = 0;
         }
-        gun_controller.timer -= time.delta_seconds();
-        gun_controller.time_since_last_shot += time.delta_seconds();
-        gun_controller.reloading_timer -= time.delta_seconds();
+        gun_controller.timer -= delta;
+        gun_controller.time_since_last_shot += delta;
+        gun_controller.reloading_timer -= delta;
+        gun_controller.sprint_ready_timer -= delta;
+        gun_controller.aiming_down_sights = action_state.pressed(Action::Aim) && !player_dead;
+        gun_controller.pending_shots.clear();
         if gun_controller.reloading_timer < 0. {
             gun_controller.shoot = false;
-            if buttons.pressed(MouseButton::Left) {
-                
-                if gun_controller.timer <= 0. {
-                    gun_controller.shoot = true;
-                    gun_controller.bullets -= 1;
-                    gun_controller.spray_index += 1;
-                    if gun_controller.bullets <= 0 {
-                        gun_controller.bullets = gun_controller.magazine_size;
-                        gun_controller.reloading_timer = gun_controller.reloading_time;
-                        gun_controller.spray_index = 0;
-                    }
-                    gun_controller.timer = gun_controller.cooldown;
-                    
+            //a click shorter than a tick still counts
+            let trigger = action_state.pressed(Action::Fire) || action_state.pressed_this_tick(Action::Fire);
+            if !trigger || player_dead || gun_controller.sprint_ready_timer > 0. {
+                //otherwise the time spent idle would come out as a burst of shots on the next pull
+                gun_controller.timer = gun_controller.timer.max(0.);
+                continue;
+            }
+            //the timer keeps its remainder, so fire rates that don't divide the tick come out right and fast guns fire several shots in one tick
+            while gun_controller.timer <= 0. && gun_controller.reloading_timer < 0. {
+                gun_controller.shoot = true;
+                noise_events.send(NoiseEvent {
+                    position: player_transform.translation,
+                    loudness: 1.,
+                    team: Some(*player_team),
+                });
+                gun_controller.bullets -= 1;
+                gun_controller.spray_index += 1;
+                let spray_index = gun_controller.spray_index;
+                gun_controller.pending_shots.push(spray_index);
+                if gun_controller.bullets <= 0 {
+                    gun_controller.bullets = gun_controller.magazine_size;
+                    gun_controller.reloading_timer = gun_controller.reloading_time;
+                    gun_controller.spray_index = 0;
                 }
+                gun_controller.timer += gun_controller.cooldown;
             }
         }
     }
@@ -91,244 +118,242 @@ pub fn update_shots(
         &mut Transform,
         Entity,
         &mut FPSCamera,
+        &Team,
     )>,
-    mut enemy_query : Query<&mut Enemy>,
-    mut head_query: Query<&mut HeadCollider>,
-    mut body_query: Query<&mut BodyCollider>,
-    mut leg_query: Query<&mut LegCollider>,
+    mut enemy_query : Query<(&mut Enemy, &Team)>,
+    friendly_fire: Res<FriendlyFire>,
+    hitbox_query: Query<&Hitbox>,
+    mut killed_events: EventWriter<EnemyKilledEvent>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    time: Res<Time>,
+    fixed_time: Res<FixedTime>,
     windows: Query<&Window, With<PrimaryWindow>>,
     rapier_context: Res<RapierContext>,
 ) {
     for (mut gun_controller, mut gun_transform, _animation_entity) in gun_query.iter_mut() {
-        if gun_controller.reloading_timer < 0. {
-            if gun_controller.shoot == true {
+        //every shot update_bullet_params let off this tick, the last one of a magazine included
+        let shots = std::mem::take(&mut gun_controller.pending_shots);
+        for spray_index in shots {
+            let window = windows.single();
 
-                let window = windows.single();
+            for (
+                camera,
+                camera_transform,
+                camera_transform_non_global,
+                entity,
+                mut fps_camera,
+                player_team,
+            ) in camera_query.iter_mut()
+            {
+                gun_controller.time_since_last_shot = 0.;
+                let Some(ray) = camera.viewport_to_world(camera_transform, Vec2::new(window.width()/2.,window.height()/2.)) else { return; };
 
-                for (
-                    camera,
-                    camera_transform,
-                    camera_transform_non_global,
-                    entity,
-                    mut fps_camera,
-                ) in camera_query.iter_mut()
-                {
-                    gun_controller.time_since_last_shot = 0.;
-                    let Some(ray) = camera.viewport_to_world(camera_transform, Vec2::new(window.width()/2.,window.height()/2.)) else { return; };
+                let x_quat =
+                    Quat::from_axis_angle(Vec3::new(0., 1., 0.), fps_camera.rotation.y);
 
-                    let x_quat =
-                        Quat::from_axis_angle(Vec3::new(0., 1., 0.), fps_camera.rotation.y);
+                let y_quat =
+                    Quat::from_axis_angle(Vec3::new(1., 0., 0.), fps_camera.rotation.x);
 
-                    let y_quat =
-                        Quat::from_axis_angle(Vec3::new(1., 0., 0.), fps_camera.rotation.x);
+                let mut camera_transform_non_corrupted = Transform::from_xyz(
+                    camera_transform_non_global.translation.x,
+                    camera_transform_non_global.translation.y,
+                    camera_transform_non_global.translation.z,
+                );
 
-                    let mut camera_transform_non_corrupted = Transform::from_xyz(
-                        camera_transform_non_global.translation.x,
-                        camera_transform_non_global.translation.y,
-                        camera_transform_non_global.translation.z,
-                    );
+                camera_transform_non_corrupted.rotation = x_quat * y_quat;
 
-                    camera_transform_non_corrupted.rotation = x_quat * y_quat;
+                let mut rng = rand::thread_rng();
 
-                    let mut rng = rand::thread_rng();
-
-                    let ray_direction;
-                    let spray_rand_movement_added =
-                        gun_controller.spray_rand + gun_controller.movement_inaccuracy;
-                    if gun_controller.spray_index > 4 {
-                        ray_direction = (camera_transform_non_corrupted.forward()
-                            + (camera_transform_non_corrupted.up()
-                                * (rng.gen_range(
-                                    -spray_rand_movement_added..spray_rand_movement_added,
-                                ) + gun_controller.spray_pattern[gun_controller.spray_index-1]
-                                    .y))
-                            + (camera_transform_non_corrupted.right()
-                                * (rng.gen_range(
-                                    -spray_rand_movement_added..spray_rand_movement_added,
-                                ) + gun_controller.spray_pattern[gun_controller.spray_index-1]
-                                    .x)))
-                            .normalize();
-                    }
-                    else if gun_controller.spray_index == 1
-                    {
-                        ray_direction = (camera_transform_non_corrupted.forward()
-                            + (camera_transform_non_corrupted.up()
-                                * (rng.gen_range(
-                                    -gun_controller.spray_rand / 200.0
-                                        ..gun_controller.spray_rand /200.0,
-                                ) + gun_controller.spray_pattern[gun_controller.spray_index-1]
-                                    .y))
-                            + (camera_transform_non_corrupted.right()
-                                * (rng.gen_range(
-                                    -spray_rand_movement_added / 24.0
-                                        ..spray_rand_movement_added / 24.0,
-                                ) + gun_controller.spray_pattern[gun_controller.spray_index-1]
-                                    .x)))
-                            .normalize();
-                    } 
-                    else {
-                        ray_direction = (camera_transform_non_corrupted.forward()
-                            + (camera_transform_non_corrupted.up()
-                                * (rng.gen_range(
-                                    -gun_controller.spray_rand / 3.0
-                                        ..gun_controller.spray_rand / 3.0,
-                                ) + gun_controller.spray_pattern[gun_controller.spray_index-1]
-                                    .y))
-                            + (camera_transform_non_corrupted.right()
-                                * (rng.gen_range(
-                                    -spray_rand_movement_added / 3.0
-                                        ..spray_rand_movement_added / 3.0,
-                                ) + gun_controller.spray_pattern[gun_controller.spray_index-1]
-                                    .x)))
-                            .normalize();
-                    }
+                let ray_direction;
+                //crouching tightens both the base spread and the movement penalty
+                let spray_rand = gun_controller.spray_rand * gun_controller.spread_multiplier;
+                let spray_rand_movement_added = spray_rand
+                    + gun_controller.movement_inaccuracy * gun_controller.spread_multiplier;
+                if spray_index > 4 {
+                    ray_direction = (camera_transform_non_corrupted.forward()
+                        + (camera_transform_non_corrupted.up()
+                            * (rng.gen_range(
+                                -spray_rand_movement_added..spray_rand_movement_added,
+                            ) + gun_controller.spray_pattern[spray_index-1]
+                                .y))
+                        + (camera_transform_non_corrupted.right()
+                            * (rng.gen_range(
+                                -spray_rand_movement_added..spray_rand_movement_added,
+                            ) + gun_controller.spray_pattern[spray_index-1]
+                                .x)))
+                        .normalize();
+                }
+                else if spray_index == 1
+                {
+                    ray_direction = (camera_transform_non_corrupted.forward()
+                        + (camera_transform_non_corrupted.up()
+                            * (rng.gen_range(
+                                -spray_rand / 200.0
+                                    ..spray_rand /200.0,
+                            ) + gun_controller.spray_pattern[spray_index-1]
+                                .y))
+                        + (camera_transform_non_corrupted.right()
+                            * (rng.gen_range(
+                                -spray_rand_movement_added / 24.0
+                                    ..spray_rand_movement_added / 24.0,
+                            ) + gun_controller.spray_pattern[spray_index-1]
+                                .x)))
+                        .normalize();
+                } 
+                else {
+                    ray_direction = (camera_transform_non_corrupted.forward()
+                        + (camera_transform_non_corrupted.up()
+                            * (rng.gen_range(
+                                -spray_rand / 3.0
+                                    ..spray_rand / 3.0,
+                            ) + gun_controller.spray_pattern[spray_index-1]
+                                .y))
+                        + (camera_transform_non_corrupted.right()
+                            * (rng.gen_range(
+                                -spray_rand_movement_added / 3.0
+                                    ..spray_rand_movement_added / 3.0,
+                            ) + gun_controller.spray_pattern[spray_index-1]
+                                .x)))
+                        .normalize();
+                }
 
-                    let hit = rapier_context.cast_ray_and_get_normal(
-                        ray.origin,
-                        ray_direction,
-                        //ray.direction,
-                        f32::MAX,
-                        true,
-                        QueryFilter::new().exclude_collider(entity),
-                    );
-                    fps_camera.recoil_shake =
-                        (ray_direction - camera_transform_non_corrupted.forward()) * 0.7;
-                    gun_controller.recoil_shake =
-                        (ray_direction - camera_transform_non_corrupted.forward()) * 1.;
+                let hit = rapier_context.cast_ray_and_get_normal(
+                    ray.origin,
+                    ray_direction,
+                    //ray.direction,
+                    f32::MAX,
+                    true,
+                    QueryFilter::new().exclude_collider(entity),
+                );
+                fps_camera.recoil_shake =
+                    (ray_direction - camera_transform_non_corrupted.forward()) * 0.7;
+                gun_controller.recoil_shake =
+                    (ray_direction - camera_transform_non_corrupted.forward()) * 1.;
 
-                    let mut placebo_camera = Transform::from_xyz(
-                        camera_transform_non_global.translation.x,
-                        camera_transform_non_global.translation.y,
-                        camera_transform_non_global.translation.z,
-                    );
+                let mut placebo_camera = Transform::from_xyz(
+                    camera_transform_non_global.translation.x,
+                    camera_transform_non_global.translation.y,
+                    camera_transform_non_global.translation.z,
+                );
 
-                    gun_controller.recoil_shake = move_towards(
-                        gun_controller.recoil_shake,
-                        Vec3::ZERO,
-                        time.delta_seconds() * gun_controller.smooth_scale,
-                    );
+                gun_controller.recoil_shake = move_towards(
+                    gun_controller.recoil_shake,
+                    Vec3::ZERO,
+                    fixed_time.period.as_secs_f32() * gun_controller.smooth_scale,
+                );
 
-                    let x_quat = Quat::from_axis_angle(
-                        Vec3::new(0., 1., 0.),
-                        fps_camera.rotation.y - gun_controller.recoil_shake.x,
-                    );
+                let x_quat = Quat::from_axis_angle(
+                    Vec3::new(0., 1., 0.),
+                    fps_camera.rotation.y - gun_controller.recoil_shake.x,
+                );
 
-                    let y_quat = Quat::from_axis_angle(
-                        Vec3::new(1., 0., 0.),
-                        fps_camera.rotation.x + gun_controller.recoil_shake.y,
-                    );
+                let y_quat = Quat::from_axis_angle(
+                    Vec3::new(1., 0., 0.),
+                    fps_camera.rotation.x + gun_controller.recoil_shake.y,
+                );
 
-                    placebo_camera.rotation = x_quat * y_quat;
+                placebo_camera.rotation = x_quat * y_quat;
 
-                    gun_transform.translation = translate_gun_position(&placebo_camera);
+                gun_transform.translation = translate_gun_position(&placebo_camera, gun_controller.ads_amount);
 
-                    gun_transform.look_at(
-                        camera_transform_non_global.translation + placebo_camera.forward() * 100.,
-                        Vec3::Y,
-                    );
-                    gun_transform.scale = Vec3::new(
-                        gun_controller.gun_scale,
-                        gun_controller.gun_scale,
-                        gun_controller.gun_scale,
-                    );
+                gun_transform.look_at(
+                    camera_transform_non_global.translation + placebo_camera.forward() * 100.,
+                    Vec3::Y,
+                );
+                gun_transform.scale = Vec3::new(
+                    gun_controller.gun_scale,
+                    gun_controller.gun_scale,
+                    gun_controller.gun_scale,
+                );
 
-                    if let Some((entity, ray_intersection)) = hit {
-                        let bullet_tracer_material = materials.add(StandardMaterial {
-                            emissive: Color::rgb_linear(100., 100., 50.0), // 4. Put something bright in a dark environment to see the effect
+                if let Some((entity, ray_intersection)) = hit {
+                    let bullet_tracer_material = materials.add(StandardMaterial {
+                        emissive: Color::rgb_linear(100., 100., 50.0), // 4. Put something bright in a dark environment to see the effect
+                        ..default()
+                    });
+                    commands.spawn((
+                        PbrBundle {
+                            transform: Transform::from_xyz(0., 100000., 0.),
+                            mesh: meshes.add(Mesh::from(shape::Cube { size: 1. })),
+                            material: bullet_tracer_material,
                             ..default()
-                        });
-                        commands.spawn((
-                            PbrBundle {
-                                transform: Transform::from_xyz(0., 100000., 0.),
-                                mesh: meshes.add(Mesh::from(shape::Cube { size: 1. })),
-                                material: bullet_tracer_material,
-                                ..default()
-                            },
-                            NotShadowCaster {},
-                            BulletTracer {
-                                direction: Vec3::new(0., 0., 0.),
-                                start_position: gun_transform.translation
-                                    + (gun_transform.up() * 0.56704 * gun_controller.gun_scale)
-                                    + (gun_transform.forward()
-                                        * 3.13735
-                                        * gun_controller.gun_scale),
+                        },
+                        NotShadowCaster {},
+                        BulletTracer {
+                            direction: Vec3::new(0., 0., 0.),
+                            start_position: gun_transform.translation
+                                + (gun_transform.up() * 0.56704 * gun_controller.gun_scale)
+                                + (gun_transform.forward()
+                                    * 3.13735
+                                    * gun_controller.gun_scale),
 
-                                end_position: ray_intersection.point,
-                                life_time: 0.3,
-                            },
-                        ));
-                        let mut spawn_bullet_hole = true;
-                        if let Ok(head) = head_query.get_mut(entity)
-                        {
-                            if let Ok(mut enemy) = enemy_query.get_mut(head.enemy_reference)
-                            {
-                                enemy.health-= 100.;
-                            }
-                            spawn_bullet_hole = false;
-                        }
-                        if let Ok(leg) = leg_query.get_mut(entity)
-                        {
-                            if let Ok(mut enemy) = enemy_query.get_mut(leg.enemy_reference)
-                            {
-                                enemy.health-= 10.;
-                            }
-                            spawn_bullet_hole = false;
-                        }
-                        if let Ok(body) = body_query.get_mut(entity)
+                            end_position: ray_intersection.point,
+                            life_time: 0.3,
+                        },
+                    ));
+                    let mut spawn_bullet_hole = true;
+                    if let Ok(hitbox) = hitbox_query.get(entity)
+                    {
+                        if let Ok((mut enemy, enemy_team)) = enemy_query.get_mut(hitbox.enemy_reference)
                         {
-                            if let Ok(mut enemy) = enemy_query.get_mut(body.enemy_reference)
-                            {
-                                enemy.health-= 20.;
+                            let damage = gun_controller.damage
+                                * hitbox.damage_multiplier
+                                * friendly_fire.damage_scale(*player_team, *enemy_team);
+                            if enemy.take_damage(damage) {
+                                killed_events.send(EnemyKilledEvent {
+                                    enemy: hitbox.enemy_reference,
+                                    bone: Some(hitbox.bone),
+                                    impulse: ray_direction * gun_controller.impact_force,
+                                    team: *player_team,
+                                    attacker_position: camera_transform_non_global.translation,
+                                });
                             }
-                            spawn_bullet_hole = false;
                         }
-                        if spawn_bullet_hole == true {
-                            let texture_handle = asset_server.load("bullet_hole.png");
+                        spawn_bullet_hole = false;
+                    }
+                    if spawn_bullet_hole == true {
+                        let texture_handle = asset_server.load("bullet_hole.png");
 
-                            // create a new quad mesh. this is what we will apply the texture to
-                            let quad_width = 0.07;
-                            let quad_handle = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(
-                                quad_width, quad_width,
-                            ))));
+                        // create a new quad mesh. this is what we will apply the texture to
+                        let quad_width = 0.07;
+                        let quad_handle = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(
+                            quad_width, quad_width,
+                        ))));
 
-                            // this material renders the texture normally
-                            let material_handle = materials.add(StandardMaterial {
-                                base_color: Color::rgba(1., 1., 1., 1.),
-                                base_color_texture: Some(texture_handle.clone()),
-                                alpha_mode: AlphaMode::Blend,
+                        // this material renders the texture normally
+                        let material_handle = materials.add(StandardMaterial {
+                            base_color: Color::rgba(1., 1., 1., 1.),
+                            base_color_texture: Some(texture_handle.clone()),
+                            alpha_mode: AlphaMode::Blend,
 
-                                cull_mode: None,
-                                unlit: true,
-                                ..default()
-                            });
+                            cull_mode: None,
+                            unlit: true,
+                            ..default()
+                        });
 
-                            let hole_position =
-                                ray_intersection.point + ray_intersection.normal * 0.045;
+                        let hole_position =
+                            ray_intersection.point + ray_intersection.normal * 0.045;
 
-                            let offseted_normal =
-                                ray_intersection.normal + Vec3::new(0.00001, 0.00001, 0.00001);
-                            let mut hole_transform = Transform::from_xyz(
-                                hole_position.x,
-                                hole_position.y,
-                                hole_position.z,
-                            );
-                            hole_transform.rotation =
-                                quaternion_look_rotation(offseted_normal, Vec3::Y);
-                            let _hole_entity_front = commands
-                                .spawn(PbrBundle {
-                                    mesh: quad_handle.clone(),
-                                    material: material_handle,
-                                    transform: hole_transform,
-                                    ..default()
-                                })
-                                .insert(NotShadowCaster)
-                                .id();
-                        }
+                        let offseted_normal =
+                            ray_intersection.normal + Vec3::new(0.00001, 0.00001, 0.00001);
+                        let mut hole_transform = Transform::from_xyz(
+                            hole_position.x,
+                            hole_position.y,
+                            hole_position.z,
+                        );
+                        hole_transform.rotation =
+                            quaternion_look_rotation(offseted_normal, Vec3::Y);
+                        let _hole_entity_front = commands
+                            .spawn(PbrBundle {
+                                mesh: quad_handle.clone(),
+                                material: material_handle,
+                                transform: hole_transform,
+                                ..default()
+                            })
+                            .insert(NotShadowCaster)
+                            .id();
                     }
                 }
             }
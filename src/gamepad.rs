@@ -0,0 +1,188 @@
+use bevy::{input::gamepad::{ButtonSettings, GamepadSettings}, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    enemy::Enemy,
+    fps_camera::FPSCamera,
+    hitbox::{HitZone, Hitbox},
+    input_map::ActionState,
+    player_health::PlayerHealth,
+    team::Team,
+};
+
+#[derive(Resource)]
+pub struct GamepadConfig {
+    //stick deflection below this reads as nothing, above the outer one as full
+    pub inner_deadzone: f32,
+    pub outer_deadzone: f32,
+    //exponent applied to look stick deflection, 1 is linear and higher gives finer aim near the centre
+    pub look_curve: f32,
+    //radians per second at full deflection
+    pub yaw_speed: f32,
+    pub pitch_speed: f32,
+    pub invert_y: bool,
+    //how far the triggers have to be pulled to count as fire and aim
+    pub trigger_threshold: f32,
+    pub aim_assist: bool,
+    //0 to 1, scales both the slowdown and the pull
+    pub aim_assist_strength: f32,
+    //half angle in degrees of the cone around the crosshair that assist works in
+    pub aim_assist_cone: f32,
+    pub aim_assist_range: f32,
+    //look speed lost over a target at full strength
+    pub aim_assist_slowdown: f32,
+    //radians per second the view is dragged towards a target at full strength
+    pub aim_assist_pull: f32,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        GamepadConfig {
+            inner_deadzone: 0.15,
+            outer_deadzone: 0.95,
+            look_curve: 2.,
+            yaw_speed: 3.5,
+            pitch_speed: 2.5,
+            invert_y: false,
+            trigger_threshold: 0.4,
+            aim_assist: true,
+            aim_assist_strength: 0.5,
+            aim_assist_cone: 5.,
+            aim_assist_range: 60.,
+            aim_assist_slowdown: 0.6,
+            aim_assist_pull: 0.6,
+        }
+    }
+}
+
+impl GamepadConfig {
+    //radial deadzone rescaled so movement starts from zero just past the inner edge, then shaped by the curve
+    pub fn shape_stick(&self, raw: Vec2, curve: f32) -> Vec2 {
+        let magnitude = raw.length();
+        if magnitude <= self.inner_deadzone {
+            return Vec2::ZERO;
+        }
+        let scaled = ((magnitude - self.inner_deadzone) / (self.outer_deadzone - self.inner_deadzone)).clamp(0., 1.);
+        raw / magnitude * scaled.powf(curve)
+    }
+}
+
+//pushes the trigger threshold into bevy's button settings so triggers press like buttons
+pub fn apply_gamepad_config(config: Res<GamepadConfig>, mut gamepad_settings: ResMut<GamepadSettings>) {
+    if !config.is_changed() {
+        return;
+    }
+    let press = config.trigger_threshold.clamp(0.05, 1.);
+    if let Ok(button_settings) = ButtonSettings::new(press, press - 0.05) {
+        gamepad_settings.default_button_settings = button_settings;
+    }
+}
+
+//what aim assist wants done to the look input this frame
+#[derive(Resource)]
+pub struct AimAssist {
+    //multiplier on stick look speed
+    pub slowdown: f32,
+    //yaw and pitch change to apply this frame
+    pub pull: Vec2,
+}
+
+impl Default for AimAssist {
+    fn default() -> Self {
+        AimAssist {
+            slowdown: 1.,
+            pull: Vec2::ZERO,
+        }
+    }
+}
+
+fn angle_difference(from: f32, to: f32) -> f32 {
+    let difference = (to - from) % std::f32::consts::TAU;
+    if difference > std::f32::consts::PI {
+        difference - std::f32::consts::TAU
+    } else if difference < -std::f32::consts::PI {
+        difference + std::f32::consts::TAU
+    } else {
+        difference
+    }
+}
+
+//finds the visible head or chest hitbox nearest the crosshair and slows and pulls the stick aim towards it
+#[allow(clippy::too_many_arguments)]
+pub fn update_aim_assist(
+    time: Res<Time>,
+    config: Res<GamepadConfig>,
+    action_state: Res<ActionState>,
+    rapier_context: Res<RapierContext>,
+    mut aim_assist: ResMut<AimAssist>,
+    player_query: Query<(Entity, &Transform, &FPSCamera, &PlayerHealth, &Team)>,
+    hitbox_query: Query<(Entity, &Hitbox, &GlobalTransform)>,
+    enemy_query: Query<(&Enemy, &Team)>,
+) {
+    *aim_assist = AimAssist::default();
+    //only helps stick aiming, and only while the player is actually using a stick
+    let stick_active = action_state.look != Vec2::ZERO || action_state.movement_from_stick;
+    if !config.aim_assist || config.aim_assist_strength <= 0. || !stick_active {
+        return;
+    }
+    let Ok((player, transform, camera, player_health, player_team)) = player_query.get_single() else {
+        return;
+    };
+    if player_health.dead {
+        return;
+    }
+
+    let eye = transform.translation;
+    let forward = Vec3::new(
+        -f32::sin(camera.rotation.y) * f32::cos(camera.rotation.x),
+        f32::sin(camera.rotation.x),
+        -f32::cos(camera.rotation.y) * f32::cos(camera.rotation.x),
+    );
+    let cone = config.aim_assist_cone.to_radians();
+
+    let mut candidates: Vec<(f32, Entity, Entity, Vec3)> = hitbox_query
+        .iter()
+        .filter(|(_, hitbox, _)| matches!(hitbox.zone, HitZone::Head | HitZone::Chest))
+        .filter(|(_, hitbox, _)| {
+            enemy_query
+                .get(hitbox.enemy_reference)
+                .is_ok_and(|(enemy, team)| enemy.health > 0. && team.is_hostile_to(*player_team))
+        })
+        .filter_map(|(entity, hitbox, global_transform)| {
+            let point = global_transform.translation();
+            let to_target = point - eye;
+            let distance = to_target.length();
+            if distance > config.aim_assist_range || distance < 0.01 {
+                return None;
+            }
+            let angle = forward.angle_between(to_target / distance);
+            (angle <= cone).then_some((angle, entity, hitbox.enemy_reference, point))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let filter = QueryFilter::new().exclude_rigid_body(player).exclude_sensors();
+    //the first candidate whose line of sight reaches its own enemy rather than a wall
+    let target = candidates.into_iter().find(|(_, _, enemy, point)| {
+        let to_target = *point - eye;
+        rapier_context
+            .cast_ray(eye, to_target.normalize(), to_target.length() + 0.1, true, filter)
+            .is_some_and(|(hit, _)| hitbox_query.get(hit).is_ok_and(|(_, hitbox, _)| hitbox.enemy_reference == *enemy))
+    });
+    let Some((angle, _, _, point)) = target else {
+        return;
+    };
+
+    let strength = config.aim_assist_strength.clamp(0., 1.);
+    let closeness = 1. - angle / cone;
+    aim_assist.slowdown = 1. - config.aim_assist_slowdown * strength * closeness;
+
+    let direction = (point - eye).normalize();
+    let target_yaw = f32::atan2(-direction.x, -direction.z);
+    let target_pitch = direction.y.clamp(-1., 1.).asin();
+    let max_step = config.aim_assist_pull * strength * time.delta_seconds();
+    aim_assist.pull = Vec2::new(
+        angle_difference(camera.rotation.y, target_yaw).clamp(-max_step, max_step),
+        (target_pitch - camera.rotation.x).clamp(-max_step, max_step),
+    );
+}
@@ -22,17 +22,33 @@ pub struct GunController {
     pub current_camera_transform: Transform,
     pub recoil_shake: Vec3,
     pub aiming_down_sights: bool,
+    //0 at the hip, 1 fully aimed down the sights
+    pub ads_amount: f32,
+    //seconds to bring the sights up
+    pub ads_time: f32,
     pub spray_rand: f32,
     pub reloading_timer: f32,
     pub reloading_time: f32,
     pub bullets: usize,
     pub movement_inaccuracy: f32,
+    //scales all spread, lowered while the player is crouched
+    pub spread_multiplier: f32,
+    //seconds after sprinting ends before this weapon can fire
+    pub sprint_to_fire_time: f32,
+    pub sprint_ready_timer: f32,
+    //spray index of each shot fired this tick, traced by update_shots
+    pub pending_shots: Vec<usize>,
+    //base damage per bullet, scaled by the multiplier of the hitbox it lands in
+    pub damage: f32,
+    //impulse given to an enemy's ragdoll by a killing shot
+    pub impact_force: f32,
 }
-pub fn translate_gun_position(camera_transform: &Transform) -> Vec3 {
+pub fn translate_gun_position(camera_transform: &Transform, ads_amount: f32) -> Vec3 {
     let mut position = camera_transform.translation;
     position += camera_transform.forward() * 0.35;
-    position += camera_transform.right() * 0.4;
-    position += camera_transform.down() * 0.3;
+    //aiming brings the gun in to the centre of the screen
+    position += camera_transform.right() * 0.4 * (1. - ads_amount);
+    position += camera_transform.down() * (0.3 - 0.1 * ads_amount);
     return position;
 }
 pub fn apply_movement_inaccuracy(
@@ -87,8 +103,15 @@ pub fn update_gun_control(
                 camera.rotation.x + gun_controller.recoil_shake.y,
             );
 
+            let ads_step = time.delta_seconds() / gun_controller.ads_time;
+            gun_controller.ads_amount = if gun_controller.aiming_down_sights {
+                (gun_controller.ads_amount + ads_step).min(1.)
+            } else {
+                (gun_controller.ads_amount - ads_step).max(0.)
+            };
+
             placebo_camera.rotation = x_quat * y_quat;
-            transform.translation = translate_gun_position(&placebo_camera);
+            transform.translation = translate_gun_position(&placebo_camera, gun_controller.ads_amount);
 
             transform.look_at(
                 camera_transform.translation + placebo_camera.forward() * 100.,
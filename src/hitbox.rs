@@ -0,0 +1,171 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::view::NoFrustumCulling,
+    utils::BoxedFuture,
+};
+use bevy_rapier3d::prelude::*;
+use serde::Deserialize;
+
+use crate::enemy::{find_descendant_by_name, Enemy};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HitZone {
+    Head,
+    Neck,
+    Chest,
+    Stomach,
+    Arms,
+    Legs,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum HitboxShape {
+    Cuboid { half_extents: [f32; 3] },
+    Capsule { half_height: f32, radius: f32 },
+    Ball { radius: f32 },
+}
+
+impl HitboxShape {
+    pub fn collider(&self) -> Collider {
+        match *self {
+            HitboxShape::Cuboid { half_extents } => {
+                Collider::cuboid(half_extents[0], half_extents[1], half_extents[2])
+            }
+            HitboxShape::Capsule { half_height, radius } => Collider::capsule_y(half_height, radius),
+            HitboxShape::Ball { radius } => Collider::ball(radius),
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        match *self {
+            HitboxShape::Cuboid { half_extents } => 8. * half_extents[0] * half_extents[1] * half_extents[2],
+            HitboxShape::Capsule { half_height, radius } => {
+                std::f32::consts::PI * radius * radius * (2. * half_height + 4. / 3. * radius)
+            }
+            HitboxShape::Ball { radius } => 4. / 3. * std::f32::consts::PI * radius * radius * radius,
+        }
+    }
+}
+
+//offsets and sizes are in the local space of the bone the hitbox is attached to
+#[derive(Deserialize, Clone)]
+pub struct HitboxDefinition {
+    pub bone: String,
+    pub zone: HitZone,
+    pub shape: HitboxShape,
+    #[serde(default)]
+    pub offset: [f32; 3],
+    pub damage_multiplier: f32,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "6b5b3f0e-2b7c-4f0c-9a4e-4f5d8a1c2e71"]
+pub struct HitboxProfile {
+    pub hitboxes: Vec<HitboxDefinition>,
+}
+
+#[derive(Default)]
+pub struct HitboxProfileLoader;
+
+impl AssetLoader for HitboxProfileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let profile: HitboxProfile = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(profile));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["hitbox.ron"]
+    }
+}
+
+//hitboxes get their own collision group so ragdoll bodies can ignore them
+pub const HITBOX_GROUP: Group = Group::GROUP_2;
+
+#[derive(Component)]
+pub struct Hitbox {
+    pub enemy_reference: Entity,
+    pub bone: Entity,
+    pub zone: HitZone,
+    pub damage_multiplier: f32,
+}
+
+fn zone_debug_color(zone: HitZone) -> Color {
+    match zone {
+        HitZone::Head => Color::GREEN,
+        HitZone::Neck => Color::YELLOW,
+        HitZone::Chest => Color::BLUE,
+        HitZone::Stomach => Color::CYAN,
+        HitZone::Arms => Color::PURPLE,
+        HitZone::Legs => Color::RED,
+    }
+}
+
+pub fn attach_hitboxes(
+    mut commands: Commands,
+    profiles: Res<Assets<HitboxProfile>>,
+    mut enemy_query: Query<(Entity, &mut Enemy, &Handle<HitboxProfile>, Option<&Name>)>,
+    children_query: Query<&Children>,
+    name_query: Query<&Name>,
+    mesh_query: Query<(), With<Handle<Mesh>>>,
+) {
+    for (entity, mut enemy, profile_handle, enemy_name) in enemy_query.iter_mut() {
+        if enemy.added_colliders {
+            continue;
+        }
+        let Some(profile) = profiles.get(profile_handle) else {
+            continue;
+        };
+        //wait for the scene to be spawned under the enemy
+        if !children_query.contains(entity) {
+            continue;
+        }
+
+        for definition in profile.hitboxes.iter() {
+            let Some(bone) = find_descendant_by_name(entity, &definition.bone, &children_query, &name_query) else {
+                warn!(
+                    "{} has no bone named {} for its hitbox",
+                    enemy_name.map_or("enemy", |n| n.as_str()),
+                    definition.bone
+                );
+                continue;
+            };
+            let collider_entity = commands
+                .spawn(definition.shape.collider())
+                .insert(TransformBundle {
+                    local: Transform::from_translation(Vec3::from(definition.offset)),
+                    ..default()
+                })
+                .insert(ColliderDebugColor(zone_debug_color(definition.zone)))
+                .insert(CollisionGroups::new(HITBOX_GROUP, Group::ALL))
+                .insert(Hitbox {
+                    enemy_reference: entity,
+                    bone,
+                    zone: definition.zone,
+                    damage_multiplier: definition.damage_multiplier,
+                })
+                .id();
+            commands.entity(bone).push_children(&[collider_entity]);
+        }
+
+        //skinned meshes get culled by their bind pose bounds, so never cull any part of the model
+        let mut stack = vec![entity];
+        while let Some(current) = stack.pop() {
+            if mesh_query.contains(current) {
+                commands.entity(current).insert(NoFrustumCulling);
+            }
+            if let Ok(children) = children_query.get(current) {
+                stack.extend(children.iter());
+            }
+        }
+        enemy.added_colliders = true;
+    }
+}
@@ -0,0 +1,521 @@
+use std::collections::BTreeMap;
+
+use bevy::{prelude::*, utils::HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{console::Console, gamepad::GamepadConfig, lock_cursor::CursorLockState, settings_file};
+
+pub const BINDINGS_PATH: &str = "config/bindings.ron";
+//binding one more than this to an action pushes out its oldest binding
+pub const MAX_BINDINGS: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Crouch,
+    Sprint,
+    LeanLeft,
+    LeanRight,
+    Fire,
+    Aim,
+    Reload,
+    ToggleCursorLock,
+    ReleaseCursor,
+    DebugHud,
+    MovementModel,
+    BodyMode,
+    FriendlyFire,
+    RebindMenu,
+    Noclip,
+    Spectate,
+    Console,
+}
+
+impl Action {
+    pub const ALL: [Action; 22] = [
+        Action::MoveForward,
+        Action::MoveBack,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::Crouch,
+        Action::Sprint,
+        Action::LeanLeft,
+        Action::LeanRight,
+        Action::Fire,
+        Action::Aim,
+        Action::Reload,
+        Action::ToggleCursorLock,
+        Action::ReleaseCursor,
+        Action::DebugHud,
+        Action::MovementModel,
+        Action::BodyMode,
+        Action::FriendlyFire,
+        Action::RebindMenu,
+        Action::Noclip,
+        Action::Spectate,
+        Action::Console,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBack => "Move back",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Jump => "Jump",
+            Action::Crouch => "Crouch",
+            Action::Sprint => "Sprint",
+            Action::LeanLeft => "Lean left",
+            Action::LeanRight => "Lean right",
+            Action::Fire => "Fire",
+            Action::Aim => "Aim down sights",
+            Action::Reload => "Reload",
+            Action::ToggleCursorLock => "Allow cursor lock",
+            Action::ReleaseCursor => "Release cursor",
+            Action::DebugHud => "Movement debug",
+            Action::MovementModel => "Movement model",
+            Action::BodyMode => "Player body mode",
+            Action::FriendlyFire => "Friendly fire",
+            Action::RebindMenu => "Key bindings",
+            Action::Noclip => "Noclip",
+            Action::Spectate => "Spectator camera",
+            Action::Console => "Console",
+        }
+    }
+
+    //menus, the console and the debug toggles, which keep working while the spectator has the gameplay input
+    pub fn is_interface(&self) -> bool {
+        matches!(
+            self,
+            Action::ToggleCursorLock
+                | Action::ReleaseCursor
+                | Action::DebugHud
+                | Action::MovementModel
+                | Action::BodyMode
+                | Action::FriendlyFire
+                | Action::RebindMenu
+                | Action::Noclip
+                | Action::Spectate
+                | Action::Console
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    //any connected gamepad, triggers count once they pass the configured threshold
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::Gamepad(button) => format!("Pad {:?}", button),
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone)]
+pub struct InputMap {
+    pub bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let defaults = [
+            (Action::MoveForward, vec![Binding::Key(KeyCode::W)]),
+            (Action::MoveBack, vec![Binding::Key(KeyCode::S)]),
+            (Action::MoveLeft, vec![Binding::Key(KeyCode::A)]),
+            (Action::MoveRight, vec![Binding::Key(KeyCode::D)]),
+            (Action::Jump, vec![Binding::Key(KeyCode::Space), Binding::Gamepad(GamepadButtonType::South)]),
+            (Action::Crouch, vec![Binding::Key(KeyCode::LControl), Binding::Gamepad(GamepadButtonType::East)]),
+            (Action::Sprint, vec![Binding::Key(KeyCode::LShift), Binding::Gamepad(GamepadButtonType::LeftThumb)]),
+            (Action::LeanLeft, vec![Binding::Key(KeyCode::Q), Binding::Gamepad(GamepadButtonType::DPadLeft)]),
+            (Action::LeanRight, vec![Binding::Key(KeyCode::E), Binding::Gamepad(GamepadButtonType::DPadRight)]),
+            (Action::Fire, vec![Binding::Mouse(MouseButton::Left), Binding::Gamepad(GamepadButtonType::RightTrigger2)]),
+            (Action::Aim, vec![Binding::Mouse(MouseButton::Right), Binding::Gamepad(GamepadButtonType::LeftTrigger2)]),
+            (Action::Reload, vec![Binding::Key(KeyCode::R), Binding::Gamepad(GamepadButtonType::West)]),
+            (Action::ToggleCursorLock, vec![Binding::Key(KeyCode::Tab)]),
+            (Action::ReleaseCursor, vec![Binding::Key(KeyCode::Escape)]),
+            (Action::DebugHud, vec![Binding::Key(KeyCode::F3)]),
+            (Action::MovementModel, vec![Binding::Key(KeyCode::F4)]),
+            (Action::BodyMode, vec![Binding::Key(KeyCode::F5)]),
+            (Action::FriendlyFire, vec![Binding::Key(KeyCode::F7)]),
+            (Action::RebindMenu, vec![Binding::Key(KeyCode::F1), Binding::Gamepad(GamepadButtonType::Select)]),
+            (Action::Noclip, vec![Binding::Key(KeyCode::V)]),
+            (Action::Spectate, vec![Binding::Key(KeyCode::F6)]),
+            (Action::Console, vec![Binding::Key(KeyCode::Grave)]),
+        ];
+        InputMap {
+            bindings: defaults.into_iter().collect(),
+        }
+    }
+}
+
+impl InputMap {
+    //falls back to the defaults for a missing or broken file, and for any action the file leaves out
+    pub fn load_or_default(path: &str) -> Self {
+        let mut input_map = InputMap::default();
+        if let Some(loaded) = settings_file::load::<InputMap>(path) {
+            input_map.bindings.extend(loaded.bindings);
+        }
+        input_map
+    }
+
+    pub fn save(&self, path: &str) {
+        settings_file::save(self, path);
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |bindings| bindings.as_slice())
+    }
+
+    pub fn add_binding(&mut self, action: Action, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_default();
+        if bindings.contains(&binding) {
+            return;
+        }
+        if bindings.len() >= MAX_BINDINGS {
+            bindings.remove(0);
+        }
+        bindings.push(binding);
+    }
+}
+
+//which actions are held this frame, worked out once from the raw keyboard, mouse and gamepad state
+#[derive(Resource, Default, Clone)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    just_released: HashSet<Action>,
+    //presses and releases since the last fixed tick, a tick can land on any frame or on none at all
+    tick_pressed: HashSet<Action>,
+    tick_released: HashSet<Action>,
+    //x is right and y is forward, keys and the left stick combined and no longer than 1
+    pub movement: Vec2,
+    pub movement_from_stick: bool,
+    //right stick after deadzones and the response curve, x is right and y is up
+    pub look: Vec2,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    //just_pressed for systems on the fixed tick
+    pub fn pressed_this_tick(&self, action: Action) -> bool {
+        self.tick_pressed.contains(&action)
+    }
+
+    pub fn released_this_tick(&self, action: Action) -> bool {
+        self.tick_released.contains(&action)
+    }
+
+    pub fn clear_tick_input(&mut self) {
+        self.tick_pressed.clear();
+        self.tick_released.clear();
+    }
+
+    //drops every action that fails keep, along with the movement and look axes
+    pub fn retain(&mut self, keep: impl Fn(&Action) -> bool) {
+        for set in [
+            &mut self.pressed,
+            &mut self.just_pressed,
+            &mut self.just_released,
+            &mut self.tick_pressed,
+            &mut self.tick_released,
+        ] {
+            set.retain(|action| keep(action));
+        }
+        self.movement = Vec2::ZERO;
+        self.movement_from_stick = false;
+        self.look = Vec2::ZERO;
+    }
+
+    //-1 to 1, holding both directions cancels out instead of one winning
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        let mut value = 0.;
+        if self.pressed(positive) {
+            value += 1.;
+        }
+        if self.pressed(negative) {
+            value -= 1.;
+        }
+        value
+    }
+}
+
+fn binding_pressed(
+    binding: &Binding,
+    key: &Input<KeyCode>,
+    mouse: &Input<MouseButton>,
+    gamepads: &Gamepads,
+    gamepad_buttons: &Input<GamepadButton>,
+) -> bool {
+    match binding {
+        Binding::Key(code) => key.pressed(*code),
+        Binding::Mouse(button) => mouse.pressed(*button),
+        Binding::Gamepad(button_type) => gamepads
+            .iter()
+            .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, *button_type))),
+    }
+}
+
+fn stick(gamepads: &Gamepads, axes: &Axis<GamepadAxis>, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+    gamepads
+        .iter()
+        .map(|gamepad| {
+            Vec2::new(
+                axes.get(GamepadAxis::new(gamepad, x)).unwrap_or(0.),
+                axes.get(GamepadAxis::new(gamepad, y)).unwrap_or(0.),
+            )
+        })
+        .max_by(|a, b| a.length().total_cmp(&b.length()))
+        .unwrap_or(Vec2::ZERO)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_action_state(
+    input_map: Res<InputMap>,
+    rebind_menu: Res<RebindMenu>,
+    console: Res<Console>,
+    gamepad_config: Res<GamepadConfig>,
+    key: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    mut action_state: ResMut<ActionState>,
+    //kept here rather than read back from the action state, which the spectator may have emptied since
+    mut previous: Local<HashSet<Action>>,
+) {
+    //the real state is tracked even under a menu, so a key still held when it closes isn't taken as a new press
+    let held: HashSet<Action> = Action::ALL
+        .into_iter()
+        .filter(|action| {
+            input_map
+                .bindings(*action)
+                .iter()
+                .any(|binding| binding_pressed(binding, &key, &mouse, &gamepads, &gamepad_buttons))
+        })
+        .collect();
+    //gameplay doesn't see any input while the bindings menu or the console is up
+    let shown = |action: &&Action| {
+        (!rebind_menu.open || **action == Action::RebindMenu) && (!console.open || **action == Action::Console)
+    };
+    action_state.pressed = held.iter().filter(shown).copied().collect();
+    action_state.just_pressed = held.difference(&previous).filter(shown).copied().collect();
+    action_state.just_released = previous.difference(&held).filter(shown).copied().collect();
+    *previous = held;
+    let ActionState { just_pressed, just_released, tick_pressed, tick_released, .. } = &mut *action_state;
+    tick_pressed.extend(just_pressed.iter());
+    tick_released.extend(just_released.iter());
+
+    if rebind_menu.open || console.open {
+        action_state.movement = Vec2::ZERO;
+        action_state.movement_from_stick = false;
+        action_state.look = Vec2::ZERO;
+        return;
+    }
+    let left_stick = gamepad_config.shape_stick(
+        stick(&gamepads, &gamepad_axes, GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+        1.,
+    );
+    let keys = Vec2::new(
+        action_state.axis(Action::MoveLeft, Action::MoveRight),
+        action_state.axis(Action::MoveBack, Action::MoveForward),
+    );
+    action_state.movement = (keys + left_stick).clamp_length_max(1.);
+    action_state.movement_from_stick = left_stick != Vec2::ZERO;
+    action_state.look = gamepad_config.shape_stick(
+        stick(&gamepads, &gamepad_axes, GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+        gamepad_config.look_curve,
+    );
+}
+
+#[derive(Resource, Default)]
+pub struct RebindMenu {
+    pub open: bool,
+    //the action waiting for its next key or button
+    pub listening: Option<Action>,
+}
+
+#[derive(Component)]
+pub struct RebindMenuRoot;
+
+#[derive(Component)]
+pub struct RebindButton(pub Action);
+
+#[derive(Component)]
+pub struct RebindText(pub Action);
+
+pub fn spawn_rebind_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let text_style = TextStyle {
+        font: asset_server.load("font.ttf"),
+        font_size: 22.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.05, 0.05, 0.05, 0.85).into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            RebindMenuRoot,
+        ))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "Click a binding, then press a key or button. Esc cancels, Backspace clears.",
+                    text_style.clone(),
+                )
+                .with_style(Style {
+                    margin: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                }),
+            );
+            for action in Action::ALL {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(560.), Val::Px(30.)),
+                                margin: UiRect::all(Val::Px(2.0)),
+                                padding: UiRect::horizontal(Val::Px(8.0)),
+                                justify_content: JustifyContent::SpaceBetween,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: Color::rgba(0.2, 0.2, 0.2, 0.9).into(),
+                            ..default()
+                        },
+                        RebindButton(action),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(action.label(), text_style.clone()));
+                        parent.spawn((TextBundle::from_section("", text_style.clone()), RebindText(action)));
+                    });
+            }
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_rebind_menu(
+    action_state: Res<ActionState>,
+    key: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut rebind_menu: ResMut<RebindMenu>,
+    mut input_map: ResMut<InputMap>,
+    mut cursor_lock_state: ResMut<CursorLockState>,
+    mut root_query: Query<&mut Visibility, With<RebindMenuRoot>>,
+    mut button_query: Query<(&Interaction, &RebindButton, &mut BackgroundColor)>,
+    mut text_query: Query<(&mut Text, &RebindText)>,
+) {
+    if action_state.just_pressed(Action::RebindMenu) && rebind_menu.listening.is_none() {
+        rebind_menu.open = !rebind_menu.open;
+        if rebind_menu.open {
+            cursor_lock_state.state = false;
+        }
+    }
+    for mut visibility in root_query.iter_mut() {
+        *visibility = if rebind_menu.open {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+    if !rebind_menu.open {
+        rebind_menu.listening = None;
+        return;
+    }
+
+    //handled before the buttons so the click that started listening isn't taken as the new binding
+    if let Some(action) = rebind_menu.listening {
+        let pressed_key = key.get_just_pressed().next().copied();
+        let pressed_button = mouse
+            .get_just_pressed()
+            .next()
+            .map(|button| Binding::Mouse(*button))
+            .or_else(|| {
+                gamepad_buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|button| Binding::Gamepad(button.button_type))
+            });
+        match (pressed_key, pressed_button) {
+            (Some(KeyCode::Escape), _) => rebind_menu.listening = None,
+            (Some(KeyCode::Back), _) => {
+                input_map.bindings.insert(action, Vec::new());
+                input_map.save(BINDINGS_PATH);
+                rebind_menu.listening = None;
+            }
+            (Some(code), _) => {
+                input_map.add_binding(action, Binding::Key(code));
+                input_map.save(BINDINGS_PATH);
+                rebind_menu.listening = None;
+            }
+            (None, Some(binding)) => {
+                input_map.add_binding(action, binding);
+                input_map.save(BINDINGS_PATH);
+                rebind_menu.listening = None;
+            }
+            (None, None) => {}
+        }
+    } else {
+        for (interaction, button, _) in button_query.iter() {
+            if *interaction == Interaction::Clicked {
+                rebind_menu.listening = Some(button.0);
+            }
+        }
+    }
+
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        *color = if rebind_menu.listening == Some(button.0) {
+            Color::rgba(0.6, 0.45, 0.1, 0.9).into()
+        } else if *interaction == Interaction::Hovered {
+            Color::rgba(0.3, 0.3, 0.3, 0.9).into()
+        } else {
+            Color::rgba(0.2, 0.2, 0.2, 0.9).into()
+        };
+    }
+    for (mut text, rebind_text) in text_query.iter_mut() {
+        text.sections[0].value = if rebind_menu.listening == Some(rebind_text.0) {
+            "press a key...".to_string()
+        } else {
+            let labels: Vec<String> = input_map.bindings(rebind_text.0).iter().map(Binding::label).collect();
+            if labels.is_empty() {
+                "unbound".to_string()
+            } else {
+                labels.join(", ")
+            }
+        };
+    }
+}
@@ -0,0 +1,149 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::{
+    footsteps::SurfaceType,
+    fps_camera::FPSCamera,
+    fps_movement::GroundState,
+    gun_control::GunController,
+    noclip::Noclip,
+    player_health::{PlayerDamageEvent, PlayerHealth},
+    traversal::Traversal,
+};
+
+pub struct Landing {
+    pub entity: Entity,
+    pub position: Vec3,
+    //fastest downward speed reached during the fall
+    pub speed: f32,
+    pub surface: SurfaceType,
+    pub damage: f32,
+}
+
+#[derive(Component)]
+pub struct FallDamage {
+    //landing slower than this never hurts
+    pub safe_speed: f32,
+    //landing at this speed or faster deals max_damage
+    pub lethal_speed: f32,
+    pub max_damage: f32,
+    //shape of the damage between the two speeds, 1 is linear and higher is gentler on short drops
+    pub curve_exponent: f32,
+    //landings below this are too soft to send an event or move the camera
+    pub min_impact_speed: f32,
+    //radians of downward pitch per unit of landing speed
+    pub camera_dip: f32,
+    pub max_camera_dip: f32,
+    pub viewmodel_kick: f32,
+    pub max_viewmodel_kick: f32,
+    pub peak_fall_speed: f32,
+    pub was_grounded: bool,
+}
+
+impl Default for FallDamage {
+    fn default() -> Self {
+        FallDamage {
+            safe_speed: 9.,
+            lethal_speed: 18.,
+            max_damage: 100.,
+            curve_exponent: 2.,
+            min_impact_speed: 3.,
+            camera_dip: 0.008,
+            max_camera_dip: 0.15,
+            viewmodel_kick: 0.012,
+            max_viewmodel_kick: 0.25,
+            peak_fall_speed: 0.,
+            was_grounded: true,
+        }
+    }
+}
+
+impl FallDamage {
+    pub fn damage(&self, speed: f32) -> f32 {
+        if speed <= self.safe_speed {
+            return 0.;
+        }
+        let t = ((speed - self.safe_speed) / (self.lethal_speed - self.safe_speed)).min(1.);
+        self.max_damage * t.powf(self.curve_exponent)
+    }
+}
+
+//keeps the fastest fall speed while airborne and resolves it on the frame the ground probe finds ground again
+#[allow(clippy::type_complexity)]
+pub fn detect_landing(
+    surface_query: Query<&SurfaceType>,
+    mut player_query: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            &GroundState,
+            &mut FallDamage,
+            &PlayerHealth,
+            Option<&Traversal>,
+        ),
+        Without<Noclip>,
+    >,
+    mut landing_events: EventWriter<Landing>,
+    mut damage_events: EventWriter<PlayerDamageEvent>,
+) {
+    for (entity, transform, velocity, ground, mut fall_damage, player_health, traversal) in player_query.iter_mut() {
+        //ladders and mantles hold the player up, and respawning teleports them, so neither counts as a fall
+        if player_health.dead || traversal.is_some_and(|traversal| !traversal.is_free()) {
+            fall_damage.peak_fall_speed = 0.;
+            fall_damage.was_grounded = ground.grounded;
+            continue;
+        }
+        if !ground.grounded {
+            fall_damage.peak_fall_speed = fall_damage.peak_fall_speed.max(-velocity.linvel.y);
+            fall_damage.was_grounded = false;
+            continue;
+        }
+        let landed = !fall_damage.was_grounded;
+        let speed = fall_damage.peak_fall_speed;
+        fall_damage.was_grounded = true;
+        fall_damage.peak_fall_speed = 0.;
+        if !landed || speed < fall_damage.min_impact_speed {
+            continue;
+        }
+
+        let damage = fall_damage.damage(speed);
+        if damage > 0. {
+            damage_events.send(PlayerDamageEvent {
+                amount: damage,
+                source: "the fall".to_string(),
+                team: None,
+            });
+        }
+        let surface = ground
+            .entity
+            .and_then(|ground_entity| surface_query.get(ground_entity).ok())
+            .copied()
+            .unwrap_or_default();
+        landing_events.send(Landing {
+            entity,
+            position: transform.translation,
+            speed,
+            surface,
+            damage,
+        });
+    }
+}
+
+//dips the view and knocks the gun down, both spring back through the existing recoil recovery
+pub fn apply_landing_impact(
+    mut landing_events: EventReader<Landing>,
+    mut camera_query: Query<(&mut FPSCamera, &FallDamage)>,
+    mut gun_query: Query<&mut GunController>,
+) {
+    for landing in landing_events.iter() {
+        let Ok((mut camera, fall_damage)) = camera_query.get_mut(landing.entity) else {
+            continue;
+        };
+        camera.recoil_shake.y -= (landing.speed * fall_damage.camera_dip).min(fall_damage.max_camera_dip);
+        let kick = (landing.speed * fall_damage.viewmodel_kick).min(fall_damage.max_viewmodel_kick);
+        for mut gun_controller in gun_query.iter_mut() {
+            gun_controller.recoil_shake.y -= kick;
+        }
+    }
+}
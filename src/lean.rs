@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    character_controller::PLAYER_HALF_EXTENTS,
+    crouch::Crouch,
+    enemy::EnemyHitboxFilter,
+    fps_camera::FPSCamera,
+    input_map::{Action, ActionState},
+    noclip::Noclip,
+    player_health::PlayerHealth,
+    slide::Slide,
+    traversal::Traversal,
+};
+
+#[derive(Component)]
+pub struct Lean {
+    //how far the body is currently shifted to the right, negative is left
+    pub offset: f32,
+    //the world space shift actually applied, un-leaning takes back exactly this
+    pub displacement: Vec3,
+    pub max_offset: f32,
+    //radians of camera roll at full lean
+    pub max_roll: f32,
+    //units per second
+    pub speed: f32,
+}
+
+impl Default for Lean {
+    fn default() -> Self {
+        Lean {
+            offset: 0.,
+            displacement: Vec3::ZERO,
+            max_offset: 0.5,
+            max_roll: 12f32.to_radians(),
+            speed: 3.,
+        }
+    }
+}
+
+//shifts the whole player sideways and rolls the camera, so the shot origin in update_shots moves with the view
+#[allow(clippy::type_complexity)]
+pub fn update_lean(
+    fixed_time: Res<FixedTime>,
+    action_state: Res<ActionState>,
+    rapier_context: Res<RapierContext>,
+    hitbox_query: Query<(), EnemyHitboxFilter>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut Lean,
+            &mut FPSCamera,
+            &PlayerHealth,
+            Option<&Crouch>,
+            Option<&Slide>,
+            Option<&Traversal>,
+        ),
+        Without<Noclip>,
+    >,
+) {
+    let not_hitbox = |collider: Entity| !hitbox_query.contains(collider);
+    for (entity, mut transform, mut lean, mut camera, player_health, crouch, slide, traversal) in
+        player_query.iter_mut()
+    {
+        let busy = player_health.dead
+            || slide.is_some_and(|slide| slide.active)
+            || traversal.is_some_and(|traversal| !traversal.is_free());
+        let target = if busy {
+            0.
+        } else {
+            action_state.axis(Action::LeanLeft, Action::LeanRight) * lean.max_offset
+        };
+
+        let step = lean.speed * fixed_time.period.as_secs_f32();
+        let mut change = (target - lean.offset).clamp(-step, step);
+        let outward = lean.offset == 0. || change.signum() == lean.offset.signum();
+        if !outward {
+            //come all the way back before leaning the other way
+            change = change.clamp(-lean.offset.abs(), lean.offset.abs());
+        }
+        if change != 0. {
+            //a lean keeps going the way it started and comes back along the same line, so turning mid lean can't
+            //walk the body anywhere
+            let direction = if lean.displacement == Vec3::ZERO {
+                Vec3::new(f32::cos(camera.rotation.y), 0., -f32::sin(camera.rotation.y)) * change.signum()
+            } else if outward {
+                lean.displacement.normalize()
+            } else {
+                -lean.displacement.normalize()
+            };
+            let half_height = crouch.map_or(PLAYER_HALF_EXTENTS.y, |crouch| crouch.half_height());
+            let half_width = crouch.map_or(PLAYER_HALF_EXTENTS.x, |crouch| crouch.half_width);
+            //a little shorter than the player so the floor doesn't count as a wall
+            let body = Collider::cuboid(half_width, half_height - 0.1, half_width);
+            let filter = QueryFilter::new()
+                .exclude_rigid_body(entity)
+                .exclude_sensors()
+                .predicate(&not_hitbox);
+            if let Some((_, toi)) = rapier_context.cast_shape(
+                transform.translation + Vec3::Y * 0.05,
+                Quat::IDENTITY,
+                direction,
+                &body,
+                change.abs(),
+                filter,
+            ) {
+                //stop just short of the wall
+                change = change.signum() * (toi.toi - 0.02).max(0.);
+            }
+            transform.translation += direction * change.abs();
+            lean.displacement += direction * change.abs();
+            lean.offset += change;
+            if lean.offset == 0. {
+                lean.displacement = Vec3::ZERO;
+            }
+        }
+
+        camera.rotation.z = -lean.offset / lean.max_offset * lean.max_roll;
+    }
+}
@@ -1,4 +1,9 @@
 use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    input_map::{Action, ActionState},
+    spectator::SpectatorInput,
+};
 #[derive(Resource)]
 pub struct CursorLockState {
     pub state: bool,
@@ -7,24 +12,25 @@ pub struct CursorLockState {
 pub fn lock_cursor_position(
     mut primary_query: Query<&mut Window, With<PrimaryWindow>>,
     mut cursor_lock_state: ResMut<CursorLockState>,
-    btn: Res<Input<MouseButton>>,
-    key: Res<Input<KeyCode>>,
+    action_state: Res<ActionState>,
+    spectator_input: Res<SpectatorInput>,
 ) {
     let Ok(mut primary) = primary_query.get_single_mut() else
     {
         return;
     };
 
-    if key.just_pressed(KeyCode::Tab) {
+    if action_state.just_pressed(Action::ToggleCursorLock) {
         cursor_lock_state.allow_lock = !cursor_lock_state.allow_lock;
     }
     if cursor_lock_state.allow_lock {
-        if btn.just_pressed(MouseButton::Left) {
+        //fire is handed to the spectator camera while it is up, clicking in still has to lock
+        if action_state.just_pressed(Action::Fire) || spectator_input.0.just_pressed(Action::Fire) {
             cursor_lock_state.state = true;
         }
     }
 
-    if key.just_pressed(KeyCode::Escape) {
+    if action_state.just_pressed(Action::ReleaseCursor) {
         cursor_lock_state.state = false;
     }
 
@@ -3,23 +3,51 @@ use std::sync::atomic::AtomicBool;
 use bevy::{
     core_pipeline::{bloom::BloomSettings, tonemapping::Tonemapping},
     prelude::*,
-    render::{view::NoFrustumCulling, render_resource::Face},
+    render::render_resource::Face,
     window::{PrimaryWindow, WindowMode, WindowResolution},
 };
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_rapier3d::prelude::*;
+use fixed_tick::AddTickEvent;
 
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 pub mod bloom;
 pub mod bullet_tracer;
+pub mod camera_effects;
+pub mod character_controller;
+pub mod console;
+pub mod crouch;
 pub mod enemy;
+pub mod enemy_ai;
+pub mod enemy_archetype;
+pub mod enemy_perception;
+pub mod fixed_tick;
+pub mod footsteps;
 pub mod fps_camera;
 pub mod fps_movement;
 pub mod fps_shooting;
+pub mod gamepad;
 pub mod gun_control;
+pub mod hitbox;
+pub mod input_map;
+pub mod landing;
+pub mod lean;
 pub mod lock_cursor;
+pub mod navmesh;
+pub mod noclip;
+pub mod player_health;
+pub mod ragdoll;
 pub mod rotation_operations;
 pub mod score_ui;
+pub mod sensitivity;
+pub mod settings_file;
+pub mod slide;
+pub mod spawn_director;
+pub mod spectator;
+pub mod sprint;
+pub mod squad;
+pub mod team;
+pub mod traversal;
 pub mod vector_operations;
 fn main() {
     App::new()
@@ -29,21 +57,188 @@ fn main() {
             allow_lock: true,
         })
         .insert_resource(MapStatus{loaded : false})
-        .add_system(fps_movement::player_movement)
-        .add_system(fps_camera::move_camera.after(fps_movement::player_movement))
+        .insert_resource(input_map::InputMap::load_or_default(input_map::BINDINGS_PATH))
+        .init_resource::<input_map::ActionState>()
+        .init_resource::<gamepad::GamepadConfig>()
+        .init_resource::<gamepad::AimAssist>()
+        .add_system(gamepad::apply_gamepad_config)
+        .add_system(
+            gamepad::update_aim_assist
+                .after(fixed_tick::interpolate_transforms)
+                .before(fps_camera::move_camera),
+        )
+        .init_resource::<input_map::RebindMenu>()
+        .add_system(
+            input_map::update_action_state
+                .in_base_set(CoreSet::PreUpdate)
+                .after(bevy::input::InputSystem),
+        )
+        .add_startup_system(input_map::spawn_rebind_menu)
+        .add_system(input_map::update_rebind_menu.before(lock_cursor::lock_cursor_position))
+        .init_resource::<console::Console>()
+        .add_event::<console::ConsoleCommand>()
+        .add_startup_system(console::spawn_console)
+        .add_system(console::update_console.before(lock_cursor::lock_cursor_position))
+        .init_resource::<noclip::NoclipSettings>()
+        .add_system(noclip::toggle_noclip.after(console::update_console))
+        .init_resource::<spectator::Spectator>()
+        .init_resource::<spectator::SpectatorInput>()
+        .add_startup_system(spectator::spawn_spectator_camera)
+        .add_system(
+            spectator::take_spectator_input
+                .in_base_set(CoreSet::PreUpdate)
+                .after(input_map::update_action_state),
+        )
+        .add_system(spectator::toggle_spectator.after(console::update_console))
+        .add_system(
+            spectator::update_spectator
+                .after(spectator::toggle_spectator)
+                .after(fixed_tick::interpolate_transforms),
+        )
+        .init_resource::<team::FriendlyFire>()
+        .add_system(team::toggle_friendly_fire.after(console::update_console))
+        .init_resource::<character_controller::PlayerBodyMode>()
+        .add_system(character_controller::toggle_player_body_mode.after(console::update_console))
+        .init_resource::<squad::Squads>()
+        .init_resource::<ragdoll::RagdollSettings>()
+        //gameplay events are sent and read on the fixed tick
+        .add_tick_event::<player_health::PlayerDamageEvent>()
+        .add_tick_event::<enemy_perception::NoiseEvent>()
+        .add_tick_event::<enemy::EnemyDamageEvent>()
+        .add_tick_event::<ragdoll::EnemyKilledEvent>()
+        //footsteps and landings are also for sounds and the camera, which run per frame
+        .add_event::<footsteps::Footstep>()
+        .add_event::<landing::Landing>()
+        //player movement, on the fixed tick
+        .add_systems(
+            (
+                character_controller::apply_player_body_mode,
+                character_controller::restore_kinematic_velocity
+                    .after(character_controller::apply_player_body_mode)
+                    .before(fps_movement::player_movement),
+                fps_movement::detect_ground
+                    .after(character_controller::restore_kinematic_velocity)
+                    .before(fps_movement::player_movement),
+                slide::update_slide
+                    .after(fps_movement::detect_ground)
+                    .before(crouch::update_crouch)
+                    .before(sprint::update_sprint),
+                crouch::update_crouch.before(fps_movement::player_movement),
+                sprint::update_sprint.after(crouch::update_crouch).before(fps_movement::player_movement),
+                traversal::update_traversal
+                    .after(fps_movement::detect_ground)
+                    .before(fps_movement::player_movement),
+                lean::update_lean
+                    .after(traversal::update_traversal)
+                    .before(fps_movement::player_movement),
+                fps_movement::player_movement,
+                noclip::noclip_flight.after(fps_movement::detect_ground),
+                character_controller::move_kinematic_player.after(fps_movement::player_movement),
+                footsteps::emit_footsteps.after(fps_movement::player_movement),
+                landing::detect_landing
+                    .after(fps_movement::detect_ground)
+                    .before(player_health::apply_player_damage),
+            )
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        //weapons, on the fixed tick
+        .add_systems(
+            (
+                sprint::apply_sprint_to_gun
+                    .after(sprint::update_sprint)
+                    .before(fps_shooting::update_bullet_params),
+                crouch::apply_crouch_spread.before(fps_shooting::update_shots),
+                gun_control::apply_movement_inaccuracy.before(fps_shooting::update_shots),
+                fps_shooting::update_bullet_params,
+                fps_shooting::update_shots.after(fps_shooting::update_bullet_params),
+            )
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        //enemies, on the fixed tick
+        .add_systems(
+            (
+                enemy_perception::hear_footsteps.after(footsteps::emit_footsteps),
+                enemy_perception::update_perception
+                    .after(enemy_perception::hear_footsteps)
+                    .after(fps_shooting::update_bullet_params),
+                squad::call_for_help.after(fps_shooting::update_shots),
+                squad::update_squads
+                    .after(enemy_perception::update_perception)
+                    .after(squad::call_for_help),
+                enemy_ai::update_enemy_behavior.after(squad::update_squads),
+                enemy::shoot_at_target.after(enemy_ai::update_enemy_behavior),
+                enemy::apply_enemy_damage.after(enemy::shoot_at_target),
+                navmesh::update_nav_paths.after(enemy_ai::update_enemy_behavior),
+                navmesh::steer_nav_agents.after(navmesh::update_nav_paths),
+                enemy::rotate_to_target.after(navmesh::steer_nav_agents),
+                spawn_director::run_spawn_director,
+                ragdoll::start_ragdolls
+                    .after(fps_shooting::update_shots)
+                    .after(enemy::apply_enemy_damage),
+            )
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        //player health, on the fixed tick
+        .add_systems(
+            (
+                player_health::apply_hazard_damage,
+                player_health::apply_player_damage
+                    .after(enemy::shoot_at_target)
+                    .after(player_health::apply_hazard_damage),
+                player_health::respawn_player.after(player_health::apply_player_damage),
+                camera_effects::apply_aim_punch.after(enemy::shoot_at_target),
+            )
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        //everything below runs once per frame on the interpolated transforms
+        .insert_resource(sensitivity::SensitivitySettings::load_or_default(sensitivity::SENSITIVITY_PATH))
+        .add_system(sensitivity::update_sensitivity_settings.after(console::update_console))
+        .insert_resource(camera_effects::CameraEffectSettings::load_or_default(camera_effects::CAMERA_EFFECTS_PATH))
+        .add_event::<camera_effects::CameraShake>()
+        .add_system(camera_effects::update_camera_effect_settings.after(console::update_console))
+        .add_system(
+            camera_effects::update_camera_effects
+                .after(fixed_tick::interpolate_transforms)
+                .before(fps_camera::move_camera)
+                .before(fps_camera::apply_fov),
+        )
+        .add_system(fps_camera::apply_fov.before(fps_camera::move_camera))
+        .add_system(fps_camera::move_camera.after(fixed_tick::interpolate_transforms))
         .add_system(gun_control::update_gun_control.after(fps_camera::move_camera))
+        .add_system(
+            landing::apply_landing_impact
+                .before(fps_camera::move_camera)
+                .before(gun_control::update_gun_control),
+        )
+        .add_system(fps_movement::update_speed_text)
+        .add_system(fps_movement::toggle_movement_model.after(console::update_console))
+        .add_system(sprint::update_stamina_bar)
         .add_system(bloom::update_bloom_settings)
-        .add_system(fps_shooting::update_shots)
-        .add_system(fps_shooting::update_bullet_params.after(fps_shooting::update_shots))
-        .add_system(fps_shooting::play_gun_animations.after(fps_shooting::update_shots))
+        .add_system(fps_shooting::play_gun_animations)
         .add_system(fps_shooting::update_targets)
         .add_system(lock_cursor::lock_cursor_position)
         .add_system(bullet_tracer::update_tracers)
         .add_system(score_ui::update_score)
         .add_system(link_animations)
         .add_system(gun_control::update_ammo_count_text)
-        .add_system(gun_control::apply_movement_inaccuracy.before(fps_shooting::update_shots))
-        .add_system(enemy::rotate_to_player.in_base_set(CoreSet::PostUpdate))
+        .add_system(enemy::update_enemies.in_base_set(CoreSet::PostUpdate))
+        .add_system(hitbox::attach_hitboxes)
+        .add_system(
+            enemy::apply_aim_pitch
+                .in_base_set(CoreSet::PostUpdate)
+                .after(bevy::animation::animation_player)
+                .before(bevy::transform::TransformSystem::TransformPropagate),
+        )
+        .add_system(enemy_perception::find_head_bones)
+        .add_system(enemy_ai::play_state_animations)
+        .add_system(player_health::update_health_text)
+        .add_system(player_health::update_death_screen)
+        .add_system(
+            ragdoll::pose_ragdolls
+                .in_base_set(CoreSet::PostUpdate)
+                .after(enemy::apply_aim_pitch)
+                .before(bevy::transform::TransformSystem::TransformPropagate),
+        )
         .add_plugins(
             DefaultPlugins
                 .set(ImagePlugin::default_nearest())
@@ -57,7 +252,16 @@ fn main() {
                     ..default()
                 }),
         )
-        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default().with_physics_scale(10.))
+        .add_plugin(
+            RapierPhysicsPlugin::<NoUserData>::default()
+                .with_physics_scale(10.)
+                .with_default_system_setup(false),
+        )
+        .add_plugin(fixed_tick::FixedTickPlugin)
+        .add_asset::<hitbox::HitboxProfile>()
+        .init_asset_loader::<hitbox::HitboxProfileLoader>()
+        .add_asset::<enemy_archetype::EnemyArchetype>()
+        .init_asset_loader::<enemy_archetype::EnemyArchetypeLoader>()
         .add_system(check_assets_ready)
         .init_resource::<AssetsLoading>()
         /*
@@ -200,69 +404,108 @@ fn setup_map(mut commands: Commands,
             ..default()
         });
     }
-    let mut enemy_vec = Vec::new();
-    enemy_vec.push(Vec3::new(38.,3.,0.02));
-    enemy_vec.push(Vec3::new(0.,3.,0.));
-    
-    enemy_vec.push(Vec3::new(10.,3.,0.02));
-    enemy_vec.push(Vec3::new(0.,0.,0.));
-
-    enemy_vec.push(Vec3::new(10.,13.,0.02));
-    enemy_vec.push(Vec3::new(0.,1.,0.));
-
-    enemy_vec.push(Vec3::new(42.,-14.,0.02));
-    enemy_vec.push(Vec3::new(0.,3.,0.));
-
-    enemy_vec.push(Vec3::new(30.,-42.,4.));
-    enemy_vec.push(Vec3::new(0.,2.,0.));
-
-    enemy_vec.push(Vec3::new(37.,13.,0.02));
-    enemy_vec.push(Vec3::new(0.,0.,0.));
-
-    enemy_vec.push(Vec3::new(9.,-11.,0.02));
-    enemy_vec.push(Vec3::new(0.,3.,0.));
-
-    enemy_vec.push(Vec3::new(12.,-25.,0.02));
-    enemy_vec.push(Vec3::new(0.,2.,0.));
-    
-    
-
-    for i in 0..enemy_vec.len()/2
+    //position, then the yaw in quarter turns and the archetype the spot always spawns if it has one
+    let enemy_spawn_vec = [
+        (Vec3::new(38.,3.,0.02), 3., None),
+        (Vec3::new(10.,3.,0.02), 0., None),
+        (Vec3::new(10.,13.,0.02), 1., Some("heavy")),
+        (Vec3::new(42.,-14.,0.02), 3., None),
+        (Vec3::new(30.,-42.,4.), 2., Some("sniper")),
+        (Vec3::new(37.,13.,0.02), 0., None),
+        (Vec3::new(9.,-11.,0.02), 3., None),
+        (Vec3::new(12.,-25.,0.02), 2., Some("rifleman")),
+    ];
+    for (position, quarter_turns, archetype) in enemy_spawn_vec.iter()
     {
-        let mut person_transform = Transform::from_xyz(enemy_vec[(2*i)].x,enemy_vec[(2*i)].z,-enemy_vec[(2*i)].y);
-        person_transform.scale = Vec3::new(2.5, 2.5, 2.5);
-        person_transform.rotate_y(1.5708*enemy_vec[(2*i)+1].y);
-
-        println!("{}",enemy_vec[(2*i)+1].y);
         commands.spawn((
-            SceneBundle {
-                transform: person_transform,
-                scene: asset_server.load("person.glb#Scene0"),
-                ..default()
+            TransformBundle::from(Transform::from_xyz(position.x,position.z,-position.y)),
+            spawn_director::EnemySpawnPoint {
+                yaw: 1.5708*quarter_turns,
+                archetype: archetype.map(|name| name.to_string()),
             },
-            enemy::Enemy {
-                respawned : true,
-                respawn_timer : 0.,
-                health:100.,
-                shoot_timer: 3.,
-                shoot_cooldown: 3.,
-                added_colliders: false,
-            },
-            NoFrustumCulling,
+        ));
+    }
+    let mix = |weights: &[(&str, f32)]| weights.iter().map(|(name, weight)| (name.to_string(), *weight)).collect();
+    commands.insert_resource(spawn_director::SpawnDirector::new(vec![
+        spawn_director::WaveDefinition { count: 4, mix: mix(&[("rusher", 1.), ("rifleman", 2.)]), delay: 3. },
+        spawn_director::WaveDefinition { count: 6, mix: mix(&[("rusher", 2.), ("rifleman", 2.), ("heavy", 1.)]), delay: 8. },
+        spawn_director::WaveDefinition {
+            count: 8,
+            mix: mix(&[("rusher", 2.), ("rifleman", 2.), ("sniper", 1.), ("heavy", 1.)]),
+            delay: 8.,
+        },
+    ]));
+
+    let player_spawn_vec = [
+        Vec3::new(0.,-4.,1.5),
+        Vec3::new(37.,-2.,1.5),
+        Vec3::new(28.,-20.,1.5),
+        Vec3::new(11.,13.,1.5),
+    ];
+    for spawn in player_spawn_vec.iter()
+    {
+        commands.spawn((
+            TransformBundle::from(Transform::from_xyz(spawn.x,spawn.z,-spawn.y)),
+            player_health::PlayerSpawnPoint,
         ));
     }
 
+    //anything that falls out of the map dies
+    commands.spawn((
+        TransformBundle::from(Transform::from_xyz(0.,-60.,0.)),
+        Collider::cuboid(1000., 10., 1000.),
+        Sensor,
+        player_health::DamageHazard {
+            name: "the void".to_string(),
+            damage_per_second: 1000.,
+        },
+    ));
+
+    let map_transform = Transform::from_xyz(0.,0., 0.).with_scale(Vec3::new(50.,50.,50.));
+    if let Some(navmesh) = navmesh::NavMesh::from_mesh(m.unwrap(), &map_transform, navmesh::NavMeshSettings::default())
+    {
+        info!("navmesh built with {} walkable cells", navmesh.spans.len());
+        commands.insert_resource(navmesh);
+    }
+
     //println!("{}",x_shape);
     commands.spawn((
         PbrBundle {
-            transform: Transform::from_xyz(0.,0., 0.).with_scale(Vec3::new(50.,50.,50.)),
+            transform: map_transform,
             mesh : meshes.add(map_mesh), 
             material : wall_mat,
             //scene: asset_server.load("map.glb#Scene0"),
             ..default()
         },
 
-    )).insert(x_shape);
+    )).insert((x_shape, footsteps::SurfaceType::Sand));
+
+    //position of the ladder's base, then its height and the yaw in quarter turns
+    let ladder_vec = [
+        (Vec3::new(30.,-39.5,0.), 4.6, 0.),
+    ];
+    let ladder_mat = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.35, 0.25, 0.15),
+        perceptual_roughness: 0.8,
+        ..default()
+    });
+    for (base, height, quarter_turns) in ladder_vec.iter()
+    {
+        let mut ladder_transform = Transform::from_xyz(base.x,base.z + height / 2.,-base.y);
+        ladder_transform.rotate_y(std::f32::consts::FRAC_PI_2 * quarter_turns);
+        commands.spawn((
+            PbrBundle {
+                transform: ladder_transform,
+                mesh: meshes.add(Mesh::from(shape::Box::new(0.8, *height, 0.1))),
+                material: ladder_mat.clone(),
+                ..default()
+            },
+            //the volume reaches out in front of the rungs so the player can grab it while facing it
+            traversal::Ladder {
+                half_extents: Vec3::new(0.5, height / 2. + 0.3, 0.5),
+            },
+        ));
+    }
     map_status.loaded = true;
 }
 fn setup_physics(
@@ -274,6 +517,13 @@ fn setup_physics(
 ) {
     let x_shape: Handle<Mesh> = asset_server.load("map.glb#Mesh0/Primitive0");
     loading.0.push(x_shape.clone_untyped());
+    //enemies can't be spawned until the archetypes have loaded
+    let archetypes = enemy_archetype::EnemyArchetypes::load(&asset_server);
+    for handle in archetypes.0.values()
+    {
+        loading.0.push(handle.clone_untyped());
+    }
+    commands.insert_resource(archetypes);
 }
 pub fn setup_ui(
     asset_server: Res<AssetServer>,
@@ -343,8 +593,101 @@ pub fn setup_ui(
                         }),
                         Label,
                     ));
+                    parent.spawn((
+                        player_health::HealthText,
+                        TextBundle::from_section(
+                            "HP 100  ARMOR 50",
+                            TextStyle {
+                                font: asset_server.load("font.ttf"),
+                                font_size: 30.0,
+                                color: Color::WHITE,
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(5.0)),
+                            ..default()
+                        }),
+                        Label,
+                    ));
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(120.), Val::Px(8.)),
+                                margin: UiRect::all(Val::Px(5.0)),
+                                align_self: AlignSelf::Center,
+                                ..default()
+                            },
+                            background_color: Color::rgba(0.15, 0.15, 0.15, 0.6).into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn((
+                                sprint::StaminaBar,
+                                NodeBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                        ..default()
+                                    },
+                                    background_color: Color::rgb(0.9, 0.8, 0.3).into(),
+                                    ..default()
+                                },
+                            ));
+                        });
                 });
         });
+
+    //movement debug readout, toggled with F3
+    let mut speed_text = TextBundle::from_section(
+        "",
+        TextStyle {
+            font: asset_server.load("font.ttf"),
+            font_size: 24.0,
+            color: Color::WHITE,
+        },
+    )
+    .with_style(Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+            left: Val::Px(5.),
+            top: Val::Px(5.),
+            ..default()
+        },
+        ..default()
+    });
+    speed_text.visibility = Visibility::Hidden;
+    commands.spawn((fps_movement::SpeedText, speed_text, Label));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    position_type: PositionType::Absolute,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.4, 0., 0., 0.5).into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            player_health::DeathScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                player_health::DeathText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("font.ttf"),
+                        font_size: 50.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_text_alignment(TextAlignment::Center),
+                Label,
+            ));
+        });
 }
 #[derive(Resource)]
 pub struct Animations(Vec<Handle<AnimationClip>>);
@@ -371,6 +714,7 @@ pub fn setup(
         color: Color::WHITE,
         brightness: 0.3,
     });
+    let player_transform = Transform::from_xyz(0.0, 1.0, 4.0);
     commands
         .spawn(SpatialBundle {
             visibility: Visibility::Visible,
@@ -391,7 +735,7 @@ pub fn setup(
                         fov: (103.0 / 360.0) * (std::f32::consts::PI * 2.0),
                         ..Default::default()
                     }),
-                    transform: Transform::from_xyz(0.0, 1.0, 4.0),
+                    transform: player_transform,
 
                     ..default()
                 },
@@ -407,14 +751,19 @@ pub fn setup(
                     linvel: Vec3::new(0.0, 0.0, 0.0),
                     angvel: Vec3::new(0.0, 0.0, 0.0),
                 },
-                Collider::cuboid(0.2, 1.4, 0.2),
+                Collider::cuboid(
+                    character_controller::PLAYER_HALF_EXTENTS.x,
+                    character_controller::PLAYER_HALF_EXTENTS.y,
+                    character_controller::PLAYER_HALF_EXTENTS.z,
+                ),
                 fps_camera::FPSCamera {
                     camera_shake_readjustment_factor: 0.3,
                     recoil_shake: Vec3::ZERO,
                     rotation: Vec3::new(0., 0., 0.),
                     speed: 300.,
                     rotate_lock: 88. * 0.0174533,
-                    sensitivity: (0.173) / 900.,
+                    fov: (103.0 / 360.0) * (std::f32::consts::PI * 2.0),
+                    ads_fov: (80.0 / 360.0) * (std::f32::consts::PI * 2.0),
                 },
                 Damping {
                     linear_damping: 4.,
@@ -424,7 +773,26 @@ pub fn setup(
                 fps_movement::FPSMovement {
                     speed: 2.2,
                     acceleration: 400.,
+                    model: fps_movement::MovementModel::Classic,
                 },
+                (
+                    fps_movement::GroundState::default(),
+                    crouch::Crouch::new(
+                        crouch::CrouchMode::Hold,
+                        character_controller::PLAYER_HALF_EXTENTS.y,
+                        character_controller::PLAYER_HALF_EXTENTS.x,
+                    ),
+                    sprint::Sprint::new(100., 1.6),
+                    traversal::Traversal::default(),
+                    slide::Slide::default(),
+                    lean::Lean::default(),
+                    footsteps::Footsteps::default(),
+                    landing::FallDamage::default(),
+                    camera_effects::CameraEffects::default(),
+                    fixed_tick::InterpolatedTransform::new(&player_transform, false),
+                ),
+                player_health::PlayerHealth::new(100., 50., 5.),
+                team::Team::PLAYERS,
             ));
         });
     let mut spray_pattern_primary = Vec::new();
@@ -462,10 +830,18 @@ pub fn setup(
         },
         gun_control::GunController {
             movement_inaccuracy: 0.,
+            spread_multiplier: 1.,
+            sprint_to_fire_time: 0.25,
+            sprint_ready_timer: 0.,
+            pending_shots: Vec::new(),
+            damage: 25.,
+            impact_force: 50.,
             reloading_time: 1.0,
             reloading_timer: 0.,
             spray_rand: 0.01,
             aiming_down_sights: false,
+            ads_amount: 0.,
+            ads_time: 0.15,
             recoil_shake: Vec3::ZERO,
             current_camera_transform: Transform::from_xyz(0.0, 0.0, 4.0),
             smooth_scale: 0.6,
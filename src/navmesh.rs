@@ -0,0 +1,790 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, VertexAttributeValues};
+
+//offsets for the four neighbour directions, neighbour index d+1 is 90 degrees from d
+const DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+//how far apart two solid spans can be before they count as separate
+const MERGE_EPSILON: f32 = 0.01;
+
+#[derive(Clone, Copy)]
+pub struct NavMeshSettings {
+    pub cell_size: f32,
+    pub agent_radius: f32,
+    pub agent_height: f32,
+    pub agent_max_climb: f32,
+    //in degrees
+    pub agent_max_slope: f32,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        NavMeshSettings {
+            cell_size: 0.25,
+            agent_radius: 0.4,
+            agent_height: 2.0,
+            agent_max_climb: 0.4,
+            agent_max_slope: 45.,
+        }
+    }
+}
+
+//a walkable floor with enough free space above it for the agent
+#[derive(Clone, Copy)]
+pub struct NavSpan {
+    pub x: u32,
+    pub z: u32,
+    pub floor: f32,
+    pub ceiling: f32,
+    pub neighbours: [Option<u32>; 4],
+}
+
+#[derive(Clone, Copy)]
+struct SolidSpan {
+    min: f32,
+    max: f32,
+    walkable: bool,
+}
+
+#[derive(Resource)]
+pub struct NavMesh {
+    pub settings: NavMeshSettings,
+    //world x/z of the corner of cell (0, 0)
+    pub origin: Vec2,
+    pub width: usize,
+    pub depth: usize,
+    //start and count into spans for every cell, spans in a column are sorted by floor
+    columns: Vec<(u32, u32)>,
+    pub spans: Vec<NavSpan>,
+}
+
+impl NavMesh {
+    pub fn from_mesh(mesh: &Mesh, transform: &Transform, settings: NavMeshSettings) -> Option<NavMesh> {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+        let matrix = transform.compute_matrix();
+        let vertices: Vec<Vec3> = positions
+            .iter()
+            .map(|p| matrix.transform_point3(Vec3::from(*p)))
+            .collect();
+        let indices: Vec<u32> = match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|i| *i as u32).collect(),
+            Some(Indices::U32(indices)) => indices.clone(),
+            None => (0..vertices.len() as u32).collect(),
+        };
+        Some(NavMesh::build(&vertices, &indices, settings))
+    }
+
+    //vertices are expected in world space
+    pub fn build(vertices: &[Vec3], indices: &[u32], settings: NavMeshSettings) -> NavMesh {
+        let cell_size = settings.cell_size;
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        for vertex in vertices {
+            min = min.min(*vertex);
+            max = max.max(*vertex);
+        }
+        if vertices.is_empty() {
+            min = Vec3::ZERO;
+            max = Vec3::ZERO;
+        }
+        let origin = Vec2::new(min.x, min.z);
+        let width = ((max.x - min.x) / cell_size).ceil() as usize + 1;
+        let depth = ((max.z - min.z) / cell_size).ceil() as usize + 1;
+
+        let solid = rasterize(vertices, indices, &settings, origin, width, depth);
+
+        //turn the solid spans into the open space above every walkable surface
+        let mut columns = vec![(0, 0); width * depth];
+        let mut spans = Vec::new();
+        for z in 0..depth {
+            for x in 0..width {
+                let column = &solid[x + z * width];
+                let start = spans.len() as u32;
+                for i in 0..column.len() {
+                    if !column[i].walkable {
+                        continue;
+                    }
+                    let floor = column[i].max;
+                    let ceiling = column.get(i + 1).map_or(f32::INFINITY, |s| s.min);
+                    if ceiling - floor >= settings.agent_height {
+                        spans.push(NavSpan {
+                            x: x as u32,
+                            z: z as u32,
+                            floor,
+                            ceiling,
+                            neighbours: [None; 4],
+                        });
+                    }
+                }
+                columns[x + z * width] = (start, spans.len() as u32 - start);
+            }
+        }
+
+        let mut navmesh = NavMesh {
+            settings,
+            origin,
+            width,
+            depth,
+            columns,
+            spans,
+        };
+        navmesh.link_neighbours();
+        navmesh.erode();
+        navmesh
+    }
+
+    fn link_neighbours(&mut self) {
+        for i in 0..self.spans.len() {
+            let span = self.spans[i];
+            for (d, (dx, dz)) in DIRECTIONS.iter().enumerate() {
+                let nx = span.x as i32 + dx;
+                let nz = span.z as i32 + dz;
+                let mut best: Option<(u32, f32)> = None;
+                for j in self.column_spans(nx, nz) {
+                    let other = &self.spans[j as usize];
+                    let step = (other.floor - span.floor).abs();
+                    let gap = f32::min(other.ceiling, span.ceiling) - f32::max(other.floor, span.floor);
+                    if step <= self.settings.agent_max_climb
+                        && gap >= self.settings.agent_height
+                        && best.is_none_or(|(_, best_step)| step < best_step)
+                    {
+                        best = Some((j, step));
+                    }
+                }
+                self.spans[i].neighbours[d] = best.map(|(j, _)| j);
+            }
+        }
+    }
+
+    //removes every span closer to an edge than the agent radius
+    fn erode(&mut self) {
+        let mut distance = vec![u32::MAX; self.spans.len()];
+        let mut queue = VecDeque::new();
+        for (i, span) in self.spans.iter().enumerate() {
+            if span.neighbours.iter().any(|n| n.is_none()) {
+                distance[i] = 0;
+                queue.push_back(i);
+            }
+        }
+        while let Some(i) = queue.pop_front() {
+            for neighbour in self.spans[i].neighbours.iter().flatten() {
+                let n = *neighbour as usize;
+                if distance[n] == u32::MAX {
+                    distance[n] = distance[i] + 1;
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        let cell_size = self.settings.cell_size;
+        let keep: Vec<bool> = distance
+            .iter()
+            .map(|d| (*d as f32 + 0.5) * cell_size >= self.settings.agent_radius)
+            .collect();
+        let mut remap = vec![None; self.spans.len()];
+        let mut spans = Vec::new();
+        for (i, span) in self.spans.iter().enumerate() {
+            if keep[i] {
+                remap[i] = Some(spans.len() as u32);
+                spans.push(*span);
+            }
+        }
+        for span in spans.iter_mut() {
+            for neighbour in span.neighbours.iter_mut() {
+                *neighbour = neighbour.and_then(|n| remap[n as usize]);
+            }
+        }
+        let mut columns = vec![(0, 0); self.width * self.depth];
+        let mut i = 0;
+        while i < spans.len() {
+            let column = spans[i].x as usize + spans[i].z as usize * self.width;
+            let start = i;
+            while i < spans.len() && spans[i].x == spans[start].x && spans[i].z == spans[start].z {
+                i += 1;
+            }
+            columns[column] = (start as u32, (i - start) as u32);
+        }
+        self.spans = spans;
+        self.columns = columns;
+    }
+
+    fn column_spans(&self, x: i32, z: i32) -> std::ops::Range<u32> {
+        if x < 0 || z < 0 || x as usize >= self.width || z as usize >= self.depth {
+            return 0..0;
+        }
+        let (start, count) = self.columns[x as usize + z as usize * self.width];
+        start..start + count
+    }
+
+    pub fn cell_of(&self, point: Vec3) -> (i32, i32) {
+        (
+            ((point.x - self.origin.x) / self.settings.cell_size).floor() as i32,
+            ((point.z - self.origin.y) / self.settings.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn span_position(&self, span: u32) -> Vec3 {
+        let span = &self.spans[span as usize];
+        Vec3::new(
+            self.origin.x + (span.x as f32 + 0.5) * self.settings.cell_size,
+            span.floor,
+            self.origin.y + (span.z as f32 + 0.5) * self.settings.cell_size,
+        )
+    }
+
+    //finds the span the point is standing on, looking a few cells around it if it is off the mesh
+    pub fn find_span(&self, point: Vec3) -> Option<u32> {
+        const SEARCH_CELLS: i32 = 8;
+        let (cx, cz) = self.cell_of(point);
+        let mut best: Option<(u32, f32)> = None;
+        for z in cz - SEARCH_CELLS..=cz + SEARCH_CELLS {
+            for x in cx - SEARCH_CELLS..=cx + SEARCH_CELLS {
+                for i in self.column_spans(x, z) {
+                    let position = self.span_position(i);
+                    let horizontal = Vec2::new(position.x - point.x, position.z - point.z).length();
+                    //prefer floors below the point over ceilings above it
+                    let vertical = if position.y <= point.y + self.settings.agent_max_climb {
+                        point.y - position.y
+                    } else {
+                        (position.y - point.y) * 4.
+                    };
+                    let score = horizontal + vertical.abs();
+                    if best.is_none_or(|(_, best_score)| score < best_score) {
+                        best = Some((i, score));
+                    }
+                }
+            }
+        }
+        best.map(|(span, _)| span)
+    }
+
+    //every walkable point that can be reached from center without leaving the radius
+    pub fn reachable_points(&self, center: Vec3, radius: f32) -> Vec<Vec3> {
+        let Some(start) = self.find_span(center) else {
+            return Vec::new();
+        };
+        let mut visited = vec![false; self.spans.len()];
+        let mut queue = VecDeque::from([start]);
+        let mut points = Vec::new();
+        visited[start as usize] = true;
+        while let Some(span) = queue.pop_front() {
+            points.push(self.span_position(span));
+            for neighbour in self.spans[span as usize].neighbours.iter().flatten() {
+                let position = self.span_position(*neighbour);
+                if !visited[*neighbour as usize]
+                    && Vec2::new(position.x - center.x, position.z - center.z).length() <= radius
+                {
+                    visited[*neighbour as usize] = true;
+                    queue.push_back(*neighbour);
+                }
+            }
+        }
+        points
+    }
+
+    fn for_each_successor(&self, span: u32, mut f: impl FnMut(u32, f32)) {
+        let cell_size = self.settings.cell_size;
+        let neighbours = self.spans[span as usize].neighbours;
+        for d in 0..4 {
+            if let Some(n) = neighbours[d] {
+                f(n, cell_size);
+            }
+            //only allow diagonals when both sides are open so paths never cut corners
+            let d2 = (d + 1) % 4;
+            if let (Some(a), Some(b)) = (neighbours[d], neighbours[d2]) {
+                let ab = self.spans[a as usize].neighbours[d2];
+                let ba = self.spans[b as usize].neighbours[d];
+                if let (Some(ab), Some(ba)) = (ab, ba) {
+                    if ab == ba {
+                        f(ab, cell_size * std::f32::consts::SQRT_2);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn find_span_path(&self, start: u32, goal: u32) -> Option<Vec<u32>> {
+        let mut cost = vec![f32::INFINITY; self.spans.len()];
+        let mut came_from = vec![u32::MAX; self.spans.len()];
+        let mut open = BinaryHeap::new();
+        let goal_position = self.span_position(goal);
+        cost[start as usize] = 0.;
+        open.push(OpenNode {
+            estimate: self.span_position(start).distance(goal_position),
+            cost: 0.,
+            span: start,
+        });
+
+        while let Some(node) = open.pop() {
+            if node.span == goal {
+                let mut path = vec![goal];
+                let mut current = goal;
+                while current != start {
+                    current = came_from[current as usize];
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let current_cost = cost[node.span as usize];
+            if node.cost > current_cost {
+                //stale entry, a cheaper route to this span was already expanded
+                continue;
+            }
+            let floor = self.spans[node.span as usize].floor;
+            self.for_each_successor(node.span, |next, step_cost| {
+                let new_cost = current_cost + step_cost + (self.spans[next as usize].floor - floor).abs();
+                if new_cost < cost[next as usize] {
+                    cost[next as usize] = new_cost;
+                    came_from[next as usize] = node.span;
+                    open.push(OpenNode {
+                        estimate: new_cost + self.span_position(next).distance(goal_position),
+                        cost: new_cost,
+                        span: next,
+                    });
+                }
+            });
+        }
+        None
+    }
+
+    //walks the cells under the straight line between two spans
+    pub fn line_walkable(&self, from: u32, to: u32) -> bool {
+        let start = self.span_position(from);
+        let end = self.span_position(to);
+        let steps = (Vec2::new(end.x - start.x, end.z - start.z).length() / (self.settings.cell_size * 0.5))
+            .ceil() as usize;
+        let mut current = from;
+        for k in 1..=steps {
+            let (tx, tz) = self.cell_of(start.lerp(end, k as f32 / steps as f32));
+            loop {
+                let span = &self.spans[current as usize];
+                let dx = (tx - span.x as i32).signum();
+                let dz = (tz - span.z as i32).signum();
+                if dx == 0 && dz == 0 {
+                    break;
+                }
+                let next = match (direction_index(dx, 0), direction_index(0, dz)) {
+                    (Some(dir_x), Some(dir_z)) => {
+                        let via_x = span.neighbours[dir_x].and_then(|n| self.spans[n as usize].neighbours[dir_z]);
+                        let via_z = span.neighbours[dir_z].and_then(|n| self.spans[n as usize].neighbours[dir_x]);
+                        match (via_x, via_z) {
+                            (Some(a), Some(b)) if a == b => Some(a),
+                            _ => None,
+                        }
+                    }
+                    (Some(dir), None) | (None, Some(dir)) => span.neighbours[dir],
+                    (None, None) => None,
+                };
+                match next {
+                    Some(next) => current = next,
+                    None => return false,
+                }
+            }
+        }
+        current == to
+    }
+
+    //removes every waypoint that can be skipped by walking in a straight line
+    pub fn string_pull(&self, path: &[u32]) -> Vec<Vec3> {
+        let mut waypoints = Vec::new();
+        let mut anchor = 0;
+        while anchor + 1 < path.len() {
+            let mut next = anchor + 1;
+            while next + 1 < path.len() && self.line_walkable(path[anchor], path[next + 1]) {
+                next += 1;
+            }
+            waypoints.push(self.span_position(path[next]));
+            anchor = next;
+        }
+        waypoints
+    }
+
+    //returns the waypoints to walk through after leaving start, the last one being the goal
+    pub fn find_path(&self, start: Vec3, goal: Vec3) -> Option<Vec<Vec3>> {
+        let start_span = self.find_span(start)?;
+        let goal_span = self.find_span(goal)?;
+        let path = self.find_span_path(start_span, goal_span)?;
+        Some(self.string_pull(&path))
+    }
+}
+
+fn direction_index(dx: i32, dz: i32) -> Option<usize> {
+    DIRECTIONS.iter().position(|d| *d == (dx, dz))
+}
+
+struct OpenNode {
+    estimate: f32,
+    cost: f32,
+    span: u32,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    //reversed so the binary heap pops the lowest estimate first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+//every column gets the vertical extent of each triangle clipped to it, merged and sorted bottom up
+fn rasterize(
+    vertices: &[Vec3],
+    indices: &[u32],
+    settings: &NavMeshSettings,
+    origin: Vec2,
+    width: usize,
+    depth: usize,
+) -> Vec<Vec<SolidSpan>> {
+    let cell_size = settings.cell_size;
+    let walkable_normal_y = settings.agent_max_slope.to_radians().cos();
+    let mut solid: Vec<Vec<SolidSpan>> = vec![Vec::new(); width * depth];
+
+    for triangle in indices.chunks_exact(3) {
+        let a = vertices[triangle[0] as usize];
+        let b = vertices[triangle[1] as usize];
+        let c = vertices[triangle[2] as usize];
+        let normal = (b - a).cross(c - a);
+        if normal.length_squared() == 0. {
+            continue;
+        }
+        //the map is rendered double sided so the winding can't be trusted
+        let walkable = normal.normalize().y.abs() >= walkable_normal_y;
+
+        let tri_min = a.min(b).min(c);
+        let tri_max = a.max(b).max(c);
+        let x0 = (((tri_min.x - origin.x) / cell_size).floor().max(0.)) as usize;
+        let x1 = (((tri_max.x - origin.x) / cell_size).floor() as usize).min(width - 1);
+        let z0 = (((tri_min.z - origin.y) / cell_size).floor().max(0.)) as usize;
+        let z1 = (((tri_max.z - origin.y) / cell_size).floor() as usize).min(depth - 1);
+
+        for z in z0..=z1 {
+            let cell_z = origin.y + z as f32 * cell_size;
+            let row = clip_polygon(&[a, b, c], 2, cell_z, true);
+            let row = clip_polygon(&row, 2, cell_z + cell_size, false);
+            if row.is_empty() {
+                continue;
+            }
+            for x in x0..=x1 {
+                let cell_x = origin.x + x as f32 * cell_size;
+                let cell = clip_polygon(&row, 0, cell_x, true);
+                let cell = clip_polygon(&cell, 0, cell_x + cell_size, false);
+                if cell.is_empty() {
+                    continue;
+                }
+                let min = cell.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+                let max = cell.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+                solid[x + z * width].push(SolidSpan { min, max, walkable });
+            }
+        }
+    }
+
+    for column in solid.iter_mut() {
+        column.sort_by(|a, b| a.min.total_cmp(&b.min));
+        let mut merged: Vec<SolidSpan> = Vec::with_capacity(column.len());
+        for span in column.iter() {
+            if let Some(last) = merged.last_mut() {
+                if span.min <= last.max + MERGE_EPSILON {
+                    if span.max > last.max + MERGE_EPSILON {
+                        last.walkable = span.walkable;
+                    } else if (span.max - last.max).abs() <= MERGE_EPSILON {
+                        last.walkable |= span.walkable;
+                    }
+                    last.max = f32::max(last.max, span.max);
+                    continue;
+                }
+            }
+            merged.push(*span);
+        }
+        *column = merged;
+    }
+    solid
+}
+
+//sutherland-hodgman against a single axis aligned plane
+fn clip_polygon(polygon: &[Vec3], axis: usize, value: f32, keep_greater: bool) -> Vec<Vec3> {
+    let inside = |p: &Vec3| {
+        if keep_greater {
+            p[axis] >= value
+        } else {
+            p[axis] <= value
+        }
+    };
+    let mut result = Vec::with_capacity(polygon.len() + 2);
+    for i in 0..polygon.len() {
+        let current = polygon[i];
+        let previous = polygon[(i + polygon.len() - 1) % polygon.len()];
+        if inside(&current) {
+            if !inside(&previous) {
+                result.push(intersect(previous, current, axis, value));
+            }
+            result.push(current);
+        } else if inside(&previous) {
+            result.push(intersect(previous, current, axis, value));
+        }
+    }
+    result
+}
+
+fn intersect(a: Vec3, b: Vec3, axis: usize, value: f32) -> Vec3 {
+    let t = (value - a[axis]) / (b[axis] - a[axis]);
+    a.lerp(b, t)
+}
+
+#[derive(Component)]
+pub struct NavAgent {
+    pub speed: f32,
+    pub destination: Option<Vec3>,
+    pub path: Vec<Vec3>,
+    pub arrive_distance: f32,
+    pub repath_interval: f32,
+    pub repath_timer: f32,
+}
+
+impl NavAgent {
+    pub fn new(speed: f32) -> Self {
+        NavAgent {
+            speed,
+            destination: None,
+            path: Vec::new(),
+            arrive_distance: 0.2,
+            repath_interval: 0.5,
+            repath_timer: 0.,
+        }
+    }
+
+    pub fn set_destination(&mut self, destination: Vec3) {
+        let moved = self
+            .destination
+            .is_none_or(|old| old.distance(destination) > self.arrive_distance);
+        self.destination = Some(destination);
+        if moved {
+            self.repath_timer = 0.;
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.destination = None;
+        self.path.clear();
+    }
+
+    pub fn is_moving(&self) -> bool {
+        !self.path.is_empty()
+    }
+}
+
+pub fn update_nav_paths(
+    fixed_time: Res<FixedTime>,
+    navmesh: Option<Res<NavMesh>>,
+    mut agent_query: Query<(&Transform, &mut NavAgent)>,
+) {
+    let Some(navmesh) = navmesh else {
+        return;
+    };
+    for (transform, mut agent) in agent_query.iter_mut() {
+        agent.repath_timer -= fixed_time.period.as_secs_f32();
+        let Some(destination) = agent.destination else {
+            continue;
+        };
+        if agent.repath_timer <= 0. {
+            agent.repath_timer = agent.repath_interval;
+            agent.path = navmesh
+                .find_path(transform.translation, destination)
+                .unwrap_or_default();
+        }
+    }
+}
+
+pub fn steer_nav_agents(fixed_time: Res<FixedTime>, mut agent_query: Query<(&mut Transform, &mut NavAgent)>) {
+    for (mut transform, mut agent) in agent_query.iter_mut() {
+        let mut step = agent.speed * fixed_time.period.as_secs_f32();
+        while step > 0. && !agent.path.is_empty() {
+            let to_waypoint = agent.path[0] - transform.translation;
+            let distance = to_waypoint.length();
+            if distance <= step {
+                transform.translation = agent.path[0];
+                agent.path.remove(0);
+                step -= distance;
+                if agent.path.is_empty() {
+                    //reached the end of the path
+                    agent.destination = None;
+                }
+            } else {
+                transform.translation += to_waypoint / distance * step;
+                step = 0.;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALL_MIN: Vec2 = Vec2::new(4.6, 0.);
+    const WALL_MAX: Vec2 = Vec2::new(5.4, 7.);
+
+    fn quad(vertices: &mut Vec<Vec3>, indices: &mut Vec<u32>, corners: [Vec3; 4]) {
+        let start = vertices.len() as u32;
+        vertices.extend(corners);
+        indices.extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+
+    //a 10x10 floor with a 3m wall across most of it and a ramp far too steep to walk up
+    fn test_navmesh() -> NavMesh {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        quad(
+            &mut vertices,
+            &mut indices,
+            [
+                Vec3::new(0., 0., 0.),
+                Vec3::new(10., 0., 0.),
+                Vec3::new(10., 0., 10.),
+                Vec3::new(0., 0., 10.),
+            ],
+        );
+
+        let (x0, x1, z0, z1, top) = (WALL_MIN.x, WALL_MAX.x, WALL_MIN.y, WALL_MAX.y, 3.);
+        quad(
+            &mut vertices,
+            &mut indices,
+            [
+                Vec3::new(x0, top, z0),
+                Vec3::new(x1, top, z0),
+                Vec3::new(x1, top, z1),
+                Vec3::new(x0, top, z1),
+            ],
+        );
+        for (a, b) in [((x0, z0), (x1, z0)), ((x1, z0), (x1, z1)), ((x1, z1), (x0, z1)), ((x0, z1), (x0, z0))] {
+            quad(
+                &mut vertices,
+                &mut indices,
+                [
+                    Vec3::new(a.0, 0., a.1),
+                    Vec3::new(b.0, 0., b.1),
+                    Vec3::new(b.0, top, b.1),
+                    Vec3::new(a.0, top, a.1),
+                ],
+            );
+        }
+
+        //about 75 degrees, well past the default 45
+        quad(
+            &mut vertices,
+            &mut indices,
+            [
+                Vec3::new(1.1, 0., 8.5),
+                Vec3::new(1.9, 3., 8.5),
+                Vec3::new(1.9, 3., 10.),
+                Vec3::new(1.1, 0., 10.),
+            ],
+        );
+
+        NavMesh::build(&vertices, &indices, NavMeshSettings::default())
+    }
+
+    fn spans_at(navmesh: &NavMesh, x: f32, z: f32) -> Vec<NavSpan> {
+        let (cx, cz) = navmesh.cell_of(Vec3::new(x, 0., z));
+        navmesh.column_spans(cx, cz).map(|i| navmesh.spans[i as usize]).collect()
+    }
+
+    fn inside_wall(point: Vec3) -> bool {
+        point.x >= WALL_MIN.x && point.x <= WALL_MAX.x && point.z >= WALL_MIN.y && point.z <= WALL_MAX.y
+    }
+
+    #[test]
+    fn open_floor_is_walkable() {
+        let navmesh = test_navmesh();
+        let spans = spans_at(&navmesh, 2.5, 3.);
+        assert_eq!(spans.len(), 1);
+        assert!(spans[0].floor.abs() < 0.01);
+        assert!(spans[0].neighbours.iter().all(|n| n.is_some()));
+    }
+
+    #[test]
+    fn wall_slope_and_edges_are_not_walkable() {
+        let navmesh = test_navmesh();
+        //the wall top is too narrow to survive erosion and the floor under it is solid
+        assert!(spans_at(&navmesh, 5., 3.).is_empty());
+        assert!(spans_at(&navmesh, 1.5, 9.).is_empty());
+        //within the agent radius of the map edge
+        assert!(spans_at(&navmesh, 0.1, 5.).is_empty());
+        //and right up against the wall
+        assert!(spans_at(&navmesh, 4.45, 3.).is_empty());
+    }
+
+    #[test]
+    fn path_goes_around_the_wall() {
+        let navmesh = test_navmesh();
+        let start = Vec3::new(2.5, 0., 3.);
+        let goal = Vec3::new(7.5, 0., 3.);
+        let waypoints = navmesh.find_path(start, goal).expect("the wall can be walked around");
+
+        let last = *waypoints.last().unwrap();
+        assert!(Vec2::new(last.x - goal.x, last.z - goal.z).length() <= navmesh.settings.cell_size);
+        assert!(waypoints.iter().any(|point| point.z > WALL_MAX.y));
+        let mut previous = start;
+        for point in waypoints.iter() {
+            assert!(point.y.abs() < 0.01);
+            for k in 0..=20 {
+                assert!(!inside_wall(previous.lerp(*point, k as f32 / 20.)));
+            }
+            previous = *point;
+        }
+    }
+
+    #[test]
+    fn string_pull_keeps_only_the_corners() {
+        let navmesh = test_navmesh();
+        let start = navmesh.find_span(Vec3::new(2.5, 0., 3.)).unwrap();
+        let goal = navmesh.find_span(Vec3::new(7.5, 0., 3.)).unwrap();
+        assert!(!navmesh.line_walkable(start, goal));
+
+        let path = navmesh.find_span_path(start, goal).unwrap();
+        let waypoints = navmesh.string_pull(&path);
+        assert!(waypoints.len() < path.len() / 4, "{:?}", waypoints);
+        //everything before the goal hugs the end of the wall, the open stretches either side need no waypoints
+        let wall_end = Vec2::new((WALL_MIN.x + WALL_MAX.x) * 0.5, WALL_MAX.y);
+        for corner in waypoints[..waypoints.len() - 1].iter() {
+            assert!(Vec2::new(corner.x, corner.z).distance(wall_end) < 1.5, "{:?}", waypoints);
+        }
+        assert_eq!(*waypoints.last().unwrap(), navmesh.span_position(goal));
+
+        //with nothing in the way the goal is the only waypoint
+        let open_goal = navmesh.find_span(Vec3::new(3.5, 0., 6.)).unwrap();
+        assert!(navmesh.line_walkable(start, open_goal));
+        let open_path = navmesh.find_span_path(start, open_goal).unwrap();
+        assert_eq!(navmesh.string_pull(&open_path), vec![navmesh.span_position(open_goal)]);
+    }
+
+    #[test]
+    fn reachable_points_stay_in_radius_and_on_this_side() {
+        let navmesh = test_navmesh();
+        let center = Vec3::new(3.5, 0., 3.);
+        let points = navmesh.reachable_points(center, 2.);
+        assert!(!points.is_empty());
+        for point in points.iter() {
+            assert!(Vec2::new(point.x - center.x, point.z - center.z).length() <= 2.);
+            //the far side of the wall is within the radius but not reachable inside it
+            assert!(point.x < WALL_MIN.x);
+        }
+    }
+}
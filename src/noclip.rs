@@ -0,0 +1,124 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    character_controller::KinematicPlayer,
+    console::{Console, ConsoleCommand},
+    fps_camera::FPSCamera,
+    fps_movement::FPSMovement,
+    input_map::{Action, ActionState},
+};
+
+#[derive(Resource)]
+pub struct NoclipSettings {
+    //units per second, the mouse wheel scales it while flying
+    pub speed: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    //applied while sprint is held
+    pub fast_multiplier: f32,
+}
+
+impl Default for NoclipSettings {
+    fn default() -> Self {
+        NoclipSettings {
+            speed: 10.,
+            min_speed: 1.,
+            max_speed: 100.,
+            fast_multiplier: 3.,
+        }
+    }
+}
+
+//on the player while noclip is on, walking, crouching, leaning, traversal and fall damage all skip a player that has it
+#[derive(Component)]
+pub struct Noclip {
+    //put back when noclip ends
+    pub gravity_scale: f32,
+}
+
+#[allow(clippy::type_complexity)]
+pub fn toggle_noclip(
+    mut commands: Commands,
+    action_state: Res<ActionState>,
+    mut command_events: EventReader<ConsoleCommand>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut console: ResMut<Console>,
+    mut settings: ResMut<NoclipSettings>,
+    mut player_query: Query<
+        (Entity, &mut GravityScale, &mut Velocity, Option<&Noclip>, Option<&mut KinematicPlayer>),
+        With<FPSMovement>,
+    >,
+) {
+    let mut toggle = action_state.just_pressed(Action::Noclip);
+    for command in command_events.iter() {
+        match command.name.as_str() {
+            "noclip" => toggle = !toggle,
+            "noclip_speed" => match command.args.first().and_then(|arg| arg.parse::<f32>().ok()) {
+                Some(speed) => {
+                    settings.speed = speed.clamp(settings.min_speed, settings.max_speed);
+                    console.print(format!("noclip speed {}", settings.speed));
+                }
+                None => console.print("usage: noclip_speed <speed>"),
+            },
+            _ => {}
+        }
+    }
+    let Ok((entity, mut gravity_scale, mut velocity, noclip, kinematic)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    if noclip.is_some() {
+        for ev in wheel_events.iter() {
+            let factor = if ev.y > 0. { 1.25 } else if ev.y < 0. { 0.8 } else { 1. };
+            settings.speed = (settings.speed * factor).clamp(settings.min_speed, settings.max_speed);
+        }
+    } else {
+        wheel_events.clear();
+    }
+    if !toggle {
+        return;
+    }
+
+    if let Some(noclip) = noclip {
+        gravity_scale.0 = noclip.gravity_scale;
+        commands.entity(entity).remove::<(Noclip, ColliderDisabled)>();
+        console.print("noclip off");
+    } else {
+        commands.entity(entity).insert((
+            Noclip {
+                gravity_scale: gravity_scale.0,
+            },
+            ColliderDisabled,
+        ));
+        gravity_scale.0 = 0.;
+        velocity.linvel = Vec3::ZERO;
+        if let Some(mut kinematic) = kinematic {
+            kinematic.velocity = Vec3::ZERO;
+        }
+        console.print("noclip on");
+    }
+}
+
+//flies along the view in every direction, jump and crouch go straight up and down
+pub fn noclip_flight(
+    fixed_time: Res<FixedTime>,
+    action_state: Res<ActionState>,
+    settings: Res<NoclipSettings>,
+    mut player_query: Query<(&mut Transform, &mut Velocity, &FPSCamera), With<Noclip>>,
+) {
+    for (mut transform, mut velocity, camera) in player_query.iter_mut() {
+        let rotation = Quat::from_axis_angle(Vec3::Y, camera.rotation.y) * Quat::from_axis_angle(Vec3::X, camera.rotation.x);
+        let wish = (rotation * Vec3::NEG_Z * action_state.movement.y
+            + rotation * Vec3::X * action_state.movement.x
+            + Vec3::Y * action_state.axis(Action::Crouch, Action::Jump))
+        .clamp_length_max(1.);
+        let speed = if action_state.pressed(Action::Sprint) {
+            settings.speed * settings.fast_multiplier
+        } else {
+            settings.speed
+        };
+        transform.translation += wish * speed * fixed_time.period.as_secs_f32();
+        velocity.linvel = Vec3::ZERO;
+    }
+}
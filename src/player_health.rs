@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    character_controller::KinematicPlayer,
+    enemy::Enemy,
+    fps_movement::FPSMovement,
+    gun_control::GunController,
+    team::{FriendlyFire, Team},
+};
+
+#[derive(Component)]
+pub struct PlayerHealth {
+    pub health: f32,
+    pub max_health: f32,
+    pub armor: f32,
+    pub max_armor: f32,
+    //fraction of incoming damage soaked up by armor while any is left
+    pub armor_absorption: f32,
+    pub dead: bool,
+    pub killer: Option<String>,
+    pub respawn_timer: f32,
+    pub respawn_delay: f32,
+}
+
+impl PlayerHealth {
+    pub fn new(max_health: f32, max_armor: f32, respawn_delay: f32) -> Self {
+        PlayerHealth {
+            health: max_health,
+            max_health,
+            armor: max_armor,
+            max_armor,
+            armor_absorption: 0.5,
+            dead: false,
+            killer: None,
+            respawn_timer: 0.,
+            respawn_delay,
+        }
+    }
+
+    //returns true if this hit killed the player
+    pub fn take_damage(&mut self, amount: f32, source: &str) -> bool {
+        if self.dead || amount <= 0. {
+            return false;
+        }
+        let absorbed = f32::min(amount * self.armor_absorption, self.armor);
+        self.armor -= absorbed;
+        self.health -= amount - absorbed;
+        if self.health <= 0. {
+            self.health = 0.;
+            self.dead = true;
+            self.killer = Some(source.to_string());
+            self.respawn_timer = self.respawn_delay;
+            return true;
+        }
+        false
+    }
+
+    pub fn reset(&mut self) {
+        self.health = self.max_health;
+        self.armor = self.max_armor;
+        self.dead = false;
+        self.killer = None;
+        self.respawn_timer = 0.;
+    }
+}
+
+pub struct PlayerDamageEvent {
+    pub amount: f32,
+    pub source: String,
+    //team of whoever dealt the damage, none for the environment
+    pub team: Option<Team>,
+}
+
+#[derive(Component)]
+pub struct DamageHazard {
+    pub name: String,
+    pub damage_per_second: f32,
+}
+
+#[derive(Component)]
+pub struct PlayerSpawnPoint;
+
+#[derive(Component)]
+pub struct HealthText;
+
+#[derive(Component)]
+pub struct DeathScreen;
+
+#[derive(Component)]
+pub struct DeathText;
+
+pub fn apply_player_damage(
+    friendly_fire: Res<FriendlyFire>,
+    mut damage_events: EventReader<PlayerDamageEvent>,
+    mut player_query: Query<(&mut PlayerHealth, &Team)>,
+) {
+    if let Ok((mut player_health, player_team)) = player_query.get_single_mut() {
+        for ev in damage_events.iter() {
+            let scale = ev.team.map_or(1., |team| friendly_fire.damage_scale(team, *player_team));
+            if player_health.take_damage(ev.amount * scale, &ev.source) {
+                info!("player killed by {}", ev.source);
+            }
+        }
+    }
+}
+
+pub fn apply_hazard_damage(
+    rapier_context: Res<RapierContext>,
+    fixed_time: Res<FixedTime>,
+    player_query: Query<Entity, With<PlayerHealth>>,
+    hazard_query: Query<(Entity, &DamageHazard)>,
+    mut damage_events: EventWriter<PlayerDamageEvent>,
+) {
+    if let Ok(player) = player_query.get_single() {
+        for (hazard_entity, hazard) in hazard_query.iter() {
+            if rapier_context.intersection_pair(player, hazard_entity) == Some(true) {
+                damage_events.send(PlayerDamageEvent {
+                    amount: hazard.damage_per_second * fixed_time.period.as_secs_f32(),
+                    source: hazard.name.clone(),
+                    team: None,
+                });
+            }
+        }
+    }
+}
+
+//picks the spawn point furthest away from any living enemy
+pub fn choose_spawn_point(spawn_points: &[Vec3], enemy_positions: &[Vec3]) -> Option<Vec3> {
+    let mut best = None;
+    let mut best_distance = f32::MIN;
+    for spawn_point in spawn_points {
+        let closest_enemy = enemy_positions
+            .iter()
+            .map(|enemy| enemy.distance(*spawn_point))
+            .fold(f32::MAX, f32::min);
+        if closest_enemy > best_distance {
+            best_distance = closest_enemy;
+            best = Some(*spawn_point);
+        }
+    }
+    best
+}
+
+pub fn respawn_player(
+    fixed_time: Res<FixedTime>,
+    mut player_query: Query<
+        (&mut PlayerHealth, &mut Transform, &mut Velocity, Option<&mut KinematicPlayer>),
+        With<FPSMovement>,
+    >,
+    spawn_query: Query<&Transform, (With<PlayerSpawnPoint>, Without<FPSMovement>)>,
+    enemy_query: Query<(&Enemy, &Transform), Without<FPSMovement>>,
+    mut gun_query: Query<&mut GunController>,
+) {
+    if let Ok((mut player_health, mut transform, mut velocity, kinematic)) = player_query.get_single_mut() {
+        if !player_health.dead {
+            return;
+        }
+        player_health.respawn_timer -= fixed_time.period.as_secs_f32();
+        if player_health.respawn_timer > 0. {
+            return;
+        }
+        let spawn_points: Vec<Vec3> = spawn_query.iter().map(|t| t.translation).collect();
+        let enemy_positions: Vec<Vec3> = enemy_query
+            .iter()
+            .filter(|(enemy, _)| enemy.health > 0.)
+            .map(|(_, t)| t.translation)
+            .collect();
+        if let Some(spawn_point) = choose_spawn_point(&spawn_points, &enemy_positions) {
+            transform.translation = spawn_point;
+        }
+        velocity.linvel = Vec3::ZERO;
+        velocity.angvel = Vec3::ZERO;
+        if let Some(mut kinematic) = kinematic {
+            kinematic.velocity = Vec3::ZERO;
+        }
+        player_health.reset();
+
+        for mut gun_controller in gun_query.iter_mut() {
+            gun_controller.bullets = gun_controller.magazine_size;
+            gun_controller.reloading_timer = 0.;
+            gun_controller.spray_index = 0;
+            gun_controller.shoot = false;
+        }
+    }
+}
+
+pub fn update_health_text(
+    player_query: Query<&PlayerHealth>,
+    mut health_text_query: Query<&mut Text, With<HealthText>>,
+) {
+    if let Ok(player_health) = player_query.get_single() {
+        if let Ok(mut text) = health_text_query.get_single_mut() {
+            text.sections[0].value = format!(
+                "HP {}  ARMOR {}",
+                player_health.health.ceil(),
+                player_health.armor.ceil()
+            );
+        }
+    }
+}
+
+pub fn update_death_screen(
+    player_query: Query<&PlayerHealth>,
+    mut death_screen_query: Query<&mut Visibility, With<DeathScreen>>,
+    mut death_text_query: Query<&mut Text, With<DeathText>>,
+) {
+    if let Ok(player_health) = player_query.get_single() {
+        if let Ok(mut visibility) = death_screen_query.get_single_mut() {
+            *visibility = if player_health.dead {
+                Visibility::Visible
+            } else {
+                Visibility::Hidden
+            };
+        }
+        if player_health.dead {
+            if let Ok(mut text) = death_text_query.get_single_mut() {
+                text.sections[0].value = format!(
+                    "KILLED BY {}\nRESPAWNING IN {:.1}",
+                    player_health
+                        .killer
+                        .as_deref()
+                        .unwrap_or("UNKNOWN")
+                        .to_uppercase(),
+                    f32::max(player_health.respawn_timer, 0.)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+use bevy_rapier3d::{prelude::*, rapier::dynamics::JointAxis};
+
+use crate::{
+    enemy::find_descendant_by_name,
+    fixed_tick::InterpolatedTransform,
+    hitbox::{HitboxProfile, HITBOX_GROUP},
+    team::Team,
+    AnimationEntityLink,
+};
+
+const RAGDOLL_GROUP: Group = Group::GROUP_3;
+
+//how far in radians each joint can bend away from the pose it died in
+const JOINT_LIMIT: f32 = 1.2;
+
+#[derive(Resource)]
+pub struct RagdollSettings {
+    //when off enemies just play their death animation
+    pub enabled: bool,
+    //spread over the bodies by the volume of their hitboxes
+    pub mass: f32,
+}
+
+impl Default for RagdollSettings {
+    fn default() -> Self {
+        RagdollSettings {
+            enabled: true,
+            mass: 80.,
+        }
+    }
+}
+
+pub struct EnemyKilledEvent {
+    pub enemy: Entity,
+    //bone the killing shot landed on, if it was known
+    pub bone: Option<Entity>,
+    pub impulse: Vec3,
+    //team of whoever landed the killing shot
+    pub team: Team,
+    pub attacker_position: Vec3,
+}
+
+#[derive(Component)]
+pub struct RagdollBody;
+
+pub struct RagdollLink {
+    pub bone: Entity,
+    pub body: Entity,
+}
+
+#[derive(Component, Default)]
+pub struct Ragdoll {
+    pub links: Vec<RagdollLink>,
+}
+
+impl Ragdoll {
+    pub fn is_active(&self) -> bool {
+        !self.links.is_empty()
+    }
+}
+
+//walks up from the bone to the closest ancestor that got a body
+fn linked_ancestor(bone: Entity, root: Entity, links: &[RagdollLink], parent_query: &Query<&Parent>) -> Option<Entity> {
+    let mut current = parent_query.get(bone).ok()?.get();
+    while current != root {
+        if let Some(link) = links.iter().find(|link| link.bone == current) {
+            return Some(link.body);
+        }
+        current = parent_query.get(current).ok()?.get();
+    }
+    None
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn start_ragdolls(
+    mut commands: Commands,
+    settings: Res<RagdollSettings>,
+    profiles: Res<Assets<HitboxProfile>>,
+    mut killed_events: EventReader<EnemyKilledEvent>,
+    mut enemy_query: Query<(&mut Ragdoll, &Handle<HitboxProfile>, &AnimationEntityLink)>,
+    mut animation_player_query: Query<&mut AnimationPlayer>,
+    children_query: Query<&Children>,
+    name_query: Query<&Name>,
+    parent_query: Query<&Parent>,
+    global_query: Query<&GlobalTransform>,
+) {
+    for event in killed_events.iter() {
+        if !settings.enabled {
+            continue;
+        }
+        let Ok((mut ragdoll, profile_handle, animation_entity)) = enemy_query.get_mut(event.enemy) else {
+            continue;
+        };
+        let Some(profile) = profiles.get(profile_handle) else {
+            continue;
+        };
+        if ragdoll.is_active() {
+            continue;
+        }
+
+        let total_volume: f32 = profile.hitboxes.iter().map(|hitbox| hitbox.shape.volume()).sum();
+        //one body per bone with hitboxes, using the hitbox shapes as its colliders
+        for definition in profile.hitboxes.iter() {
+            let Some(bone) = find_descendant_by_name(event.enemy, &definition.bone, &children_query, &name_query) else {
+                continue;
+            };
+            let body = match ragdoll.links.iter().find(|link| link.bone == bone) {
+                Some(link) => link.body,
+                None => {
+                    let Ok(bone_global) = global_query.get(bone) else {
+                        continue;
+                    };
+                    let body_transform = bone_global.compute_transform();
+                    let body = commands
+                        .spawn((
+                            RigidBody::Dynamic,
+                            Velocity::default(),
+                            TransformBundle::from(body_transform),
+                            InterpolatedTransform::new(&body_transform, true),
+                            RagdollBody,
+                        ))
+                        .id();
+                    ragdoll.links.push(RagdollLink { bone, body });
+                    body
+                }
+            };
+            let collider = commands
+                .spawn((
+                    definition.shape.collider(),
+                    TransformBundle::from(Transform::from_translation(Vec3::from(definition.offset))),
+                    ColliderMassProperties::Mass(settings.mass * definition.shape.volume() / total_volume),
+                    CollisionGroups::new(RAGDOLL_GROUP, Group::ALL - HITBOX_GROUP),
+                ))
+                .id();
+            commands.entity(body).push_children(&[collider]);
+        }
+
+        //join every body to its closest linked ancestor, bones without one hang off the first body
+        let Some(root_body) = ragdoll.links.iter().find(|link| {
+            linked_ancestor(link.bone, event.enemy, &ragdoll.links, &parent_query).is_none()
+        }).map(|link| link.body) else {
+            continue;
+        };
+        for link in ragdoll.links.iter() {
+            if link.body == root_body {
+                continue;
+            }
+            let parent_body = linked_ancestor(link.bone, event.enemy, &ragdoll.links, &parent_query).unwrap_or(root_body);
+            let parent_bone = ragdoll.links.iter().find(|other| other.body == parent_body).map(|other| other.bone);
+            let (Some(parent_global), Ok(bone_global)) = (parent_bone.and_then(|bone| global_query.get(bone).ok()), global_query.get(link.bone)) else {
+                continue;
+            };
+            let (_, parent_rotation, parent_translation) = parent_global.to_scale_rotation_translation();
+            let anchor = parent_rotation.inverse() * (bone_global.translation() - parent_translation);
+            let mut joint = SphericalJointBuilder::new()
+                .local_anchor1(anchor)
+                .local_anchor2(Vec3::ZERO)
+                .limits(JointAxis::AngX, [-JOINT_LIMIT, JOINT_LIMIT])
+                .limits(JointAxis::AngY, [-JOINT_LIMIT, JOINT_LIMIT])
+                .limits(JointAxis::AngZ, [-JOINT_LIMIT, JOINT_LIMIT])
+                .build();
+            joint.set_contacts_enabled(false);
+            commands.entity(link.body).insert(ImpulseJoint::new(parent_body, joint));
+        }
+
+        let hit_body = ragdoll
+            .links
+            .iter()
+            .find(|link| Some(link.bone) == event.bone)
+            .map_or(root_body, |link| link.body);
+        commands.entity(hit_body).insert(ExternalImpulse {
+            impulse: event.impulse,
+            torque_impulse: Vec3::ZERO,
+        });
+
+        //the bodies drive the bones from now on
+        if let Ok(mut player) = animation_player_query.get_mut(animation_entity.0) {
+            player.pause();
+        }
+    }
+}
+
+//runs after the animation and physics writeback so the bodies have the last word on the bone poses
+pub fn pose_ragdolls(
+    enemy_query: Query<&Ragdoll>,
+    body_query: Query<&Transform, With<RagdollBody>>,
+    mut bone_query: Query<(&mut Transform, &Parent), Without<RagdollBody>>,
+    global_query: Query<&GlobalTransform>,
+) {
+    for ragdoll in enemy_query.iter() {
+        for link in ragdoll.links.iter() {
+            let Ok(body_transform) = body_query.get(link.body) else {
+                continue;
+            };
+            let Ok((mut bone_transform, parent)) = bone_query.get_mut(link.bone) else {
+                continue;
+            };
+            let parent_matrix = match ragdoll.links.iter().find(|other| other.bone == parent.get()) {
+                Some(parent_link) => match body_query.get(parent_link.body) {
+                    Ok(parent_body) => parent_body.compute_matrix(),
+                    Err(_) => continue,
+                },
+                None => match global_query.get(parent.get()) {
+                    Ok(parent_global) => parent_global.compute_matrix(),
+                    Err(_) => continue,
+                },
+            };
+            *bone_transform = Transform::from_matrix(parent_matrix.inverse() * body_transform.compute_matrix());
+        }
+    }
+}
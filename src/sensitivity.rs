@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    console::{Console, ConsoleCommand},
+    settings_file,
+};
+
+pub const SENSITIVITY_PATH: &str = "config/sensitivity.ron";
+
+//games whose in-game sensitivity number can be typed in as is, they only differ in degrees turned per count
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum SensitivityPreset {
+    //cs and the other source games
+    Source,
+    Valorant,
+    Overwatch,
+    Apex,
+    //degrees per count typed in directly
+    Custom,
+}
+
+impl SensitivityPreset {
+    pub const ALL: [SensitivityPreset; 5] = [
+        SensitivityPreset::Source,
+        SensitivityPreset::Valorant,
+        SensitivityPreset::Overwatch,
+        SensitivityPreset::Apex,
+        SensitivityPreset::Custom,
+    ];
+
+    //degrees turned per count at an in-game sensitivity of 1
+    pub fn yaw(&self) -> Option<f32> {
+        match self {
+            SensitivityPreset::Source => Some(0.022),
+            SensitivityPreset::Valorant => Some(0.07),
+            SensitivityPreset::Overwatch => Some(0.0066),
+            SensitivityPreset::Apex => Some(0.022),
+            SensitivityPreset::Custom => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SensitivityPreset::Source => "source",
+            SensitivityPreset::Valorant => "valorant",
+            SensitivityPreset::Overwatch => "overwatch",
+            SensitivityPreset::Apex => "apex",
+            SensitivityPreset::Custom => "custom",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum MouseAccel {
+    Off,
+    //gain grows in a straight line with mouse speed
+    Linear,
+    //gain grows with mouse speed raised to accel_exponent
+    Power,
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensitivitySettings {
+    pub preset: SensitivityPreset,
+    //the number from the preset's game, multiplied by its yaw
+    pub game_sensitivity: f32,
+    //degrees per count used by the custom preset
+    pub custom_degrees_per_count: f32,
+    pub yaw_multiplier: f32,
+    pub pitch_multiplier: f32,
+    //fraction of the way from the screen centre to its edge where a flick lands on the same spot at the hip and
+    //aimed in, 0 keeps the tracking speed the same relative to the zoom and 1 matches the screen edge
+    pub ads_monitor_distance: f32,
+    //applied on top of the matched ratio while aiming down sights
+    pub ads_multiplier: f32,
+    pub accel: MouseAccel,
+    //gain added per count per millisecond of mouse speed above the offset
+    pub accel_rate: f32,
+    pub accel_exponent: f32,
+    //counts per millisecond the mouse has to move faster than before accel starts
+    pub accel_offset: f32,
+    //highest gain accel can reach
+    pub accel_cap: f32,
+}
+
+impl Default for SensitivitySettings {
+    fn default() -> Self {
+        SensitivitySettings {
+            preset: SensitivityPreset::Source,
+            game_sensitivity: 0.5,
+            custom_degrees_per_count: 0.011,
+            yaw_multiplier: 1.,
+            pitch_multiplier: 1.,
+            ads_monitor_distance: 0.,
+            ads_multiplier: 1.,
+            accel: MouseAccel::Off,
+            accel_rate: 0.1,
+            accel_exponent: 2.,
+            accel_offset: 0.,
+            accel_cap: 3.,
+        }
+    }
+}
+
+impl SensitivitySettings {
+    //missing or broken files fall back to the defaults, fields the file leaves out keep theirs
+    pub fn load_or_default(path: &str) -> Self {
+        settings_file::load(path).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &str) {
+        settings_file::save(self, path);
+    }
+
+    pub fn degrees_per_count(&self) -> f32 {
+        self.preset
+            .yaw()
+            .map_or(self.custom_degrees_per_count, |yaw| yaw * self.game_sensitivity)
+    }
+
+    //radians turned per count on each axis, x is yaw and y is pitch
+    pub fn radians_per_count(&self) -> Vec2 {
+        Vec2::new(self.yaw_multiplier, self.pitch_multiplier) * self.degrees_per_count().to_radians()
+    }
+
+    //how much slower to turn at the current fov than at the hip, fovs are vertical like bevy's and aspect is width over height
+    pub fn zoom_ratio(&self, hip_fov: f32, current_fov: f32, aspect: f32, ads_amount: f32) -> f32 {
+        //the extra multiplier comes in with the sights so it doesn't snap on at the first frame of aiming
+        let extra = 1. + (self.ads_multiplier - 1.) * ads_amount;
+        if current_fov >= hip_fov {
+            return extra;
+        }
+        let hip = (hip_fov * 0.5).tan() * aspect;
+        let current = (current_fov * 0.5).tan() * aspect;
+        let distance = self.ads_monitor_distance.clamp(0., 1.);
+        let matched = if distance <= 0. {
+            //the limit of the formula below as the distance goes to 0
+            current / hip
+        } else {
+            (distance * current).atan() / (distance * hip).atan()
+        };
+        matched * extra
+    }
+
+    //gain for the mouse moving at this many counts per millisecond
+    pub fn accel_gain(&self, counts_per_ms: f32) -> f32 {
+        let speed = (counts_per_ms - self.accel_offset).max(0.);
+        let gain = match self.accel {
+            MouseAccel::Off => return 1.,
+            MouseAccel::Linear => 1. + self.accel_rate * speed,
+            MouseAccel::Power => 1. + (self.accel_rate * speed).powf(self.accel_exponent),
+        };
+        gain.min(self.accel_cap.max(1.))
+    }
+}
+
+//console commands for everything in the sensitivity settings, each change is saved straight away
+pub fn update_sensitivity_settings(
+    mut command_events: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    mut settings: ResMut<SensitivitySettings>,
+) {
+    for command in command_events.iter() {
+        let numbers: Vec<f32> = command.args.iter().filter_map(|arg| arg.parse().ok()).collect();
+        match (command.name.as_str(), numbers.as_slice()) {
+            ("sensitivity", [value]) => settings.game_sensitivity = value.max(0.),
+            ("sensitivity", _) => {
+                //the same turn speed written the way each game would have it
+                let degrees_per_count = settings.degrees_per_count();
+                for preset in SensitivityPreset::ALL {
+                    if let Some(yaw) = preset.yaw() {
+                        console.print(format!("{} {:.3}", preset.label(), degrees_per_count / yaw));
+                    }
+                }
+                console.print(format!("{:.4} degrees per count", degrees_per_count));
+                continue;
+            }
+            ("sensitivity_preset", _) => {
+                let name = command.args.first().map_or("", String::as_str);
+                let Some(preset) = SensitivityPreset::ALL.into_iter().find(|preset| preset.label() == name) else {
+                    console.print("usage: sensitivity_preset source|valorant|overwatch|apex|custom");
+                    continue;
+                };
+                //the custom preset picks up where the old one was so switching to it doesn't change the feel
+                if preset == SensitivityPreset::Custom {
+                    settings.custom_degrees_per_count = settings.degrees_per_count();
+                }
+                settings.preset = preset;
+            }
+            ("degrees_per_count", [value]) => {
+                settings.preset = SensitivityPreset::Custom;
+                settings.custom_degrees_per_count = value.max(0.);
+            }
+            ("sensitivity_axes", [yaw, pitch]) => {
+                settings.yaw_multiplier = *yaw;
+                settings.pitch_multiplier = *pitch;
+            }
+            ("ads_sensitivity", [distance]) => settings.ads_monitor_distance = distance.clamp(0., 1.),
+            ("ads_sensitivity", [distance, multiplier]) => {
+                settings.ads_monitor_distance = distance.clamp(0., 1.);
+                settings.ads_multiplier = multiplier.max(0.);
+            }
+            ("mouse_accel", _) => {
+                settings.accel = match command.args.first().map(String::as_str) {
+                    Some("off") => MouseAccel::Off,
+                    Some("linear") => MouseAccel::Linear,
+                    Some("power") => MouseAccel::Power,
+                    _ => {
+                        console.print("usage: mouse_accel off|linear|power [rate] [cap]");
+                        continue;
+                    }
+                };
+                if let Some(rate) = numbers.first() {
+                    settings.accel_rate = rate.max(0.);
+                }
+                if let Some(cap) = numbers.get(1) {
+                    settings.accel_cap = cap.max(1.);
+                }
+            }
+            ("degrees_per_count" | "sensitivity_axes" | "ads_sensitivity", _) => {
+                console.print("wrong number of values, try help");
+                continue;
+            }
+            _ => continue,
+        }
+        console.print(format!(
+            "{} sensitivity {} ({:.4} degrees per count)",
+            settings.preset.label(),
+            settings.game_sensitivity,
+            settings.degrees_per_count()
+        ));
+        settings.save(SENSITIVITY_PATH);
+    }
+}
@@ -0,0 +1,29 @@
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+//reads a ron settings file, a missing file gives none and a broken one warns and gives none
+pub fn load<T: DeserializeOwned>(path: &str) -> Option<T> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    match ron::from_str::<T>(&contents) {
+        Ok(loaded) => Some(loaded),
+        Err(error) => {
+            warn!("couldn't read {}: {}", path, error);
+            None
+        }
+    }
+}
+
+//writes a ron settings file, making its folder if needed
+pub fn save<T: Serialize>(value: &T, path: &str) {
+    let result = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())
+        .and_then(|contents| {
+            if let Some(directory) = std::path::Path::new(path).parent() {
+                std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+            }
+            std::fs::write(path, contents).map_err(|error| error.to_string())
+        });
+    if let Err(error) = result {
+        warn!("couldn't save {}: {}", path, error);
+    }
+}
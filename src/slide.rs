@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::{
+    crouch::Crouch,
+    fps_movement::GroundState,
+    input_map::{Action, ActionState},
+    player_health::PlayerHealth,
+    sprint::Sprint,
+};
+
+#[derive(Component)]
+pub struct Slide {
+    pub active: bool,
+    //speed gained the moment a slide starts
+    pub boost: f32,
+    //deceleration in units per second squared on flat ground
+    pub friction: f32,
+    //how hard slopes pull the slide downhill
+    pub slope_acceleration: f32,
+    //the slide ends once it drops below this
+    pub end_speed: f32,
+    pub cooldown: f32,
+    pub cooldown_timer: f32,
+}
+
+impl Default for Slide {
+    fn default() -> Self {
+        Slide {
+            active: false,
+            boost: 1.5,
+            friction: 3.,
+            slope_acceleration: 9.81,
+            end_speed: 1.,
+            cooldown: 0.6,
+            cooldown_timer: 0.,
+        }
+    }
+}
+
+//velocity change for one frame of sliding, friction plus whatever the slope adds
+pub fn slide_velocity(slide: &Slide, ground: &GroundState, velocity: Vec3, delta: f32) -> Vec3 {
+    let mut horizontal = Vec3::new(velocity.x, 0., velocity.z);
+    let speed = horizontal.length();
+    if speed > 0. {
+        horizontal *= (speed - slide.friction * delta).max(0.) / speed;
+    }
+    //gravity along the ground plane, zero on flat ground
+    let gravity = Vec3::NEG_Y * slide.slope_acceleration;
+    let downhill = gravity - ground.normal * gravity.dot(ground.normal);
+    horizontal += Vec3::new(downhill.x, 0., downhill.z) * delta;
+    Vec3::new(horizontal.x, velocity.y, horizontal.z)
+}
+
+//crouching out of a sprint turns into a slide that lasts until crouch is let go, friction wins or the player leaves the ground
+pub fn update_slide(
+    fixed_time: Res<FixedTime>,
+    action_state: Res<ActionState>,
+    mut player_query: Query<(&mut Slide, &mut Velocity, &Crouch, &Sprint, &GroundState, &PlayerHealth)>,
+) {
+    for (mut slide, mut velocity, crouch, sprint, ground, player_health) in player_query.iter_mut() {
+        slide.cooldown_timer -= fixed_time.period.as_secs_f32();
+        let speed = Vec2::new(velocity.linvel.x, velocity.linvel.z).length();
+
+        if slide.active {
+            //uphill slopes bleed the speed off through the same end check
+            if player_health.dead || !crouch.wants_crouch || !ground.grounded || speed < slide.end_speed {
+                slide.active = false;
+                slide.cooldown_timer = slide.cooldown;
+            }
+            continue;
+        }
+
+        //sprint is still set from last frame as crouching ends it
+        if sprint.sprinting && ground.grounded && action_state.pressed_this_tick(Action::Crouch) && slide.cooldown_timer <= 0. {
+            slide.active = true;
+            velocity.linvel.x *= slide.boost;
+            velocity.linvel.z *= slide.boost;
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use bevy::{prelude::*, render::view::NoFrustumCulling};
+use bevy_rapier3d::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::{
+    enemy::{Enemy, EnemyFacing, EnemyHitboxFilter},
+    enemy_ai::{EnemyBehavior, EnemyStimuli},
+    enemy_archetype::{EnemyArchetype, EnemyArchetypes},
+    enemy_perception::{Perception, EYE_HEIGHT},
+    fixed_tick::InterpolatedTransform,
+    hitbox::HitboxProfile,
+    navmesh::NavAgent,
+    player_health::PlayerHealth,
+    ragdoll::Ragdoll,
+    squad::{SquadMember, SquadRole},
+    team::Team,
+};
+
+#[derive(Component)]
+pub struct EnemySpawnPoint {
+    //radians, the way enemies spawned here face until they have something to look at
+    pub yaw: f32,
+    //always spawns this archetype, otherwise one is picked from the wave's mix
+    pub archetype: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct WaveDefinition {
+    pub count: usize,
+    //archetype names and their relative weights
+    pub mix: Vec<(String, f32)>,
+    //seconds of quiet before the wave starts
+    pub delay: f32,
+}
+
+impl WaveDefinition {
+    pub fn pick_archetype(&self, rng: &mut impl rand::Rng) -> Option<&str> {
+        self.mix
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .ok()
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+#[derive(Resource)]
+pub struct SpawnDirector {
+    pub waves: Vec<WaveDefinition>,
+    //keeps counting past the last definition, which then repeats
+    pub wave: usize,
+    pub wave_in_progress: bool,
+    pub wave_timer: f32,
+    pub spawned_this_wave: usize,
+    pub max_alive: usize,
+    pub spawn_interval: f32,
+    pub spawn_timer: f32,
+    //spawn points closer than this to the player are skipped
+    pub min_player_distance: f32,
+    //added to the difficulty multiplier for every wave survived
+    pub difficulty_per_wave: f32,
+    pub total_spawned: usize,
+    //each wave is split into squads of up to this many enemies
+    pub squad_size: usize,
+    pub squad_count: u32,
+}
+
+impl SpawnDirector {
+    pub fn new(waves: Vec<WaveDefinition>) -> Self {
+        let first_delay = waves.first().map_or(0., |wave| wave.delay);
+        SpawnDirector {
+            waves,
+            wave: 0,
+            wave_in_progress: false,
+            wave_timer: first_delay,
+            spawned_this_wave: 0,
+            max_alive: 5,
+            spawn_interval: 1.5,
+            spawn_timer: 0.,
+            min_player_distance: 12.,
+            difficulty_per_wave: 0.2,
+            total_spawned: 0,
+            squad_size: 3,
+            squad_count: 0,
+        }
+    }
+
+    pub fn current_wave(&self) -> Option<&WaveDefinition> {
+        self.waves.get(self.wave).or(self.waves.last())
+    }
+
+    pub fn difficulty(&self) -> f32 {
+        1. + self.wave as f32 * self.difficulty_per_wave
+    }
+}
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    archetype: &EnemyArchetype,
+    position: Vec3,
+    yaw: f32,
+    name: String,
+    difficulty: f32,
+) -> Entity {
+    let mut person_transform = Transform::from_translation(position);
+    person_transform.scale = Vec3::splat(archetype.scale);
+    person_transform.rotate_y(yaw);
+    let max_health = archetype.health * difficulty;
+
+    commands.spawn((
+        Name::new(name),
+        SceneBundle {
+            transform: person_transform,
+            scene: asset_server.load(archetype.model.as_str()),
+            ..default()
+        },
+        Enemy {
+            died: false,
+            corpse_timer: 0.,
+            health: max_health,
+            armor: archetype.armor,
+            armor_absorption: 0.5,
+            shoot_timer: archetype.behavior.reaction_time,
+            shoot_cooldown: archetype.weapon.cooldown / difficulty,
+            added_colliders: false,
+            shot_damage: archetype.weapon.damage * difficulty,
+            attack_range: archetype.weapon.range,
+            accuracy: archetype.accuracy,
+        },
+        NavAgent::new(archetype.move_speed),
+        EnemyBehavior::new(position, max_health, archetype.behavior.clone()),
+        EnemyStimuli::default(),
+        Perception::default(),
+        EnemyFacing::new(yaw, archetype.turn_rate),
+        archetype.team,
+        asset_server.load::<HitboxProfile, _>(archetype.hitbox_profile.as_str()),
+        Ragdoll::default(),
+        InterpolatedTransform::new(&person_transform, true),
+        NoFrustumCulling,
+    )).id()
+}
+
+//true if level geometry hides the point from the eye, checked at both the feet and the head of an enemy standing there
+fn hidden_from(
+    rapier_context: &RapierContext,
+    hitbox_query: &Query<(), EnemyHitboxFilter>,
+    eye: Vec3,
+    point: Vec3,
+) -> bool {
+    let not_hitbox = |collider: Entity| !hitbox_query.contains(collider);
+    let filter = QueryFilter::only_fixed().predicate(&not_hitbox);
+    [point + Vec3::Y * 0.3, point + Vec3::Y * EYE_HEIGHT].iter().all(|target| {
+        let direction = *target - eye;
+        rapier_context
+            .cast_ray(eye, direction.normalize(), direction.length(), true, filter)
+            .is_some()
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_spawn_director(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    asset_server: Res<AssetServer>,
+    rapier_context: Res<RapierContext>,
+    director: Option<ResMut<SpawnDirector>>,
+    archetypes: Res<EnemyArchetypes>,
+    archetype_assets: Res<Assets<EnemyArchetype>>,
+    spawn_point_query: Query<(&Transform, &EnemySpawnPoint)>,
+    player_query: Query<(&Transform, &Team), With<PlayerHealth>>,
+    enemy_query: Query<(&Transform, &Enemy, &Team)>,
+    hitbox_query: Query<(), EnemyHitboxFilter>,
+) {
+    let Some(mut director) = director else {
+        return;
+    };
+    let Ok((player_transform, player_team)) = player_query.get_single() else {
+        return;
+    };
+    //friendly bots don't count towards the cap or keep a wave going
+    let alive: Vec<Vec3> = enemy_query
+        .iter()
+        .filter(|(_, enemy, team)| enemy.health > 0. && team.is_hostile_to(*player_team))
+        .map(|(transform, ..)| transform.translation)
+        .collect();
+
+    if !director.wave_in_progress {
+        director.wave_timer -= fixed_time.period.as_secs_f32();
+        if director.wave_timer <= 0. {
+            director.wave_in_progress = true;
+            director.spawned_this_wave = 0;
+            director.spawn_timer = 0.;
+            info!("wave {} starting, difficulty {:.1}", director.wave + 1, director.difficulty());
+        }
+        return;
+    }
+
+    let Some(wave) = director.current_wave().cloned() else {
+        return;
+    };
+    if director.spawned_this_wave >= wave.count {
+        if alive.is_empty() {
+            director.wave += 1;
+            director.wave_in_progress = false;
+            director.wave_timer = director.current_wave().map_or(0., |next| next.delay);
+        }
+        return;
+    }
+
+    director.spawn_timer -= fixed_time.period.as_secs_f32();
+    if director.spawn_timer > 0. || alive.len() >= director.max_alive {
+        return;
+    }
+
+    let eye = player_transform.translation;
+    let candidates: Vec<(&Transform, &EnemySpawnPoint)> = spawn_point_query
+        .iter()
+        .filter(|(transform, _)| transform.translation.distance(eye) >= director.min_player_distance)
+        .filter(|(transform, _)| alive.iter().all(|enemy| enemy.distance(transform.translation) > 1.5))
+        .filter(|(transform, _)| hidden_from(&rapier_context, &hitbox_query, eye, transform.translation))
+        .collect();
+    let mut rng = rand::thread_rng();
+    //try again shortly if the player can see every free spawn point
+    let Some((spawn_transform, spawn_point)) = candidates.choose(&mut rng) else {
+        director.spawn_timer = 0.5;
+        return;
+    };
+
+    let archetype_name = spawn_point
+        .archetype
+        .as_deref()
+        .or_else(|| wave.pick_archetype(&mut rng))
+        .unwrap_or_default();
+    let Some(archetype) = archetypes.get(archetype_name, &archetype_assets) else {
+        warn!("no enemy archetype named {}", archetype_name);
+        director.spawn_timer = director.spawn_interval;
+        return;
+    };
+
+    director.total_spawned += 1;
+    if director.spawned_this_wave % director.squad_size == 0 {
+        director.squad_count += 1;
+    }
+    let enemy = spawn_enemy(
+        &mut commands,
+        &asset_server,
+        archetype,
+        spawn_transform.translation,
+        spawn_point.yaw,
+        format!("{} {}", archetype.display_name, director.total_spawned),
+        director.difficulty(),
+    );
+    //roles are handed out once the squad is known
+    commands.entity(enemy).insert(SquadMember {
+        squad: director.squad_count,
+        role: SquadRole::Hold,
+    });
+    director.spawned_this_wave += 1;
+    director.spawn_timer = director.spawn_interval;
+}
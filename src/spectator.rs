@@ -0,0 +1,353 @@
+use bevy::{
+    core_pipeline::{bloom::BloomSettings, tonemapping::Tonemapping},
+    input::mouse::{MouseMotion, MouseWheel},
+    prelude::*,
+};
+
+use crate::{
+    console::{Console, ConsoleCommand},
+    enemy::{Enemy, MODEL_FORWARD},
+    enemy_perception::EYE_HEIGHT,
+    fps_camera::FPSCamera,
+    input_map::{Action, ActionState},
+    lock_cursor::CursorLockState,
+    sensitivity::SensitivitySettings,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpectatorMode {
+    //flies around on its own
+    Free,
+    //sits behind the target and turns with it
+    Follow,
+    //circles the target, the mouse moves around it
+    Orbit,
+}
+
+impl SpectatorMode {
+    pub fn next(&self) -> SpectatorMode {
+        match self {
+            SpectatorMode::Free => SpectatorMode::Follow,
+            SpectatorMode::Follow => SpectatorMode::Orbit,
+            SpectatorMode::Orbit => SpectatorMode::Free,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SpectatorMode::Free => "free",
+            SpectatorMode::Follow => "follow",
+            SpectatorMode::Orbit => "orbit",
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Spectator {
+    pub active: bool,
+    pub mode: SpectatorMode,
+    pub target: Option<Entity>,
+    pub yaw: f32,
+    pub pitch: f32,
+    //units per second in free mode, the mouse wheel scales it
+    pub speed: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    //applied while sprint is held
+    pub fast_multiplier: f32,
+    //the mouse wheel zooms between min and max in orbit mode
+    pub orbit_distance: f32,
+    pub min_orbit_distance: f32,
+    pub max_orbit_distance: f32,
+    pub follow_distance: f32,
+    pub follow_height: f32,
+    //radians per second at full right stick
+    pub stick_speed: f32,
+}
+
+impl Default for Spectator {
+    fn default() -> Self {
+        Spectator {
+            active: false,
+            mode: SpectatorMode::Free,
+            target: None,
+            yaw: 0.,
+            pitch: 0.,
+            speed: 10.,
+            min_speed: 1.,
+            max_speed: 100.,
+            fast_multiplier: 3.,
+            orbit_distance: 4.,
+            min_orbit_distance: 1.5,
+            max_orbit_distance: 20.,
+            follow_distance: 3.,
+            follow_height: 0.8,
+            stick_speed: 3.,
+        }
+    }
+}
+
+//what the player pressed this frame while spectating, gameplay sees none of it
+#[derive(Resource, Default)]
+pub struct SpectatorInput(pub ActionState);
+
+#[derive(Component)]
+pub struct SpectatorCamera;
+
+#[derive(Component)]
+pub struct SpectatorText;
+
+pub fn spawn_spectator_camera(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        Camera3dBundle {
+            camera: Camera {
+                hdr: true,
+                is_active: false,
+                ..default()
+            },
+            tonemapping: Tonemapping::TonyMcMapface,
+            projection: Projection::Perspective(PerspectiveProjection {
+                fov: (103.0 / 360.0) * (std::f32::consts::PI * 2.0),
+                ..Default::default()
+            }),
+            ..default()
+        },
+        BloomSettings {
+            intensity: 0.2,
+            ..default()
+        },
+        SpectatorCamera,
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("font.ttf"),
+                font_size: 24.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        SpectatorText,
+    ));
+}
+
+//moves the frame's input over to the spectator, leaving only the interface actions for everything else
+pub fn take_spectator_input(
+    spectator: Res<Spectator>,
+    mut action_state: ResMut<ActionState>,
+    mut spectator_input: ResMut<SpectatorInput>,
+) {
+    if !spectator.active {
+        spectator_input.0 = ActionState::default();
+        return;
+    }
+    spectator_input.0 = action_state.clone();
+    action_state.retain(Action::is_interface);
+}
+
+//alive enemies in a stable order, so next and previous always walk the same way round
+fn spectator_targets(enemy_query: &Query<(Entity, &Transform, &Enemy), Without<SpectatorCamera>>) -> Vec<Entity> {
+    let mut targets: Vec<Entity> = enemy_query
+        .iter()
+        .filter(|(_, _, enemy)| enemy.health > 0.)
+        .map(|(entity, ..)| entity)
+        .collect();
+    targets.sort();
+    targets
+}
+
+fn cycle_target(current: Option<Entity>, targets: &[Entity], step: isize) -> Option<Entity> {
+    if targets.is_empty() {
+        return None;
+    }
+    let index = match current.and_then(|current| targets.iter().position(|target| *target == current)) {
+        Some(index) => (index as isize + step).rem_euclid(targets.len() as isize) as usize,
+        None => 0,
+    };
+    Some(targets[index])
+}
+
+#[allow(clippy::type_complexity)]
+pub fn toggle_spectator(
+    action_state: Res<ActionState>,
+    mut command_events: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    mut spectator: ResMut<Spectator>,
+    enemy_query: Query<(Entity, &Transform, &Enemy), Without<SpectatorCamera>>,
+    mut player_query: Query<(&Transform, &FPSCamera, &mut Camera), Without<SpectatorCamera>>,
+    mut spectator_query: Query<(&mut Transform, &mut Camera), (With<SpectatorCamera>, Without<FPSCamera>)>,
+) {
+    let mut toggle = action_state.just_pressed(Action::Spectate);
+    let mut mode = None;
+    let mut next = false;
+    for command in command_events.iter() {
+        if command.name != "spectate" {
+            continue;
+        }
+        match command.args.first().map(String::as_str) {
+            None => toggle = !toggle,
+            Some("free") => mode = Some(SpectatorMode::Free),
+            Some("follow") => mode = Some(SpectatorMode::Follow),
+            Some("orbit") => mode = Some(SpectatorMode::Orbit),
+            Some("next") => next = true,
+            Some(_) => console.print("usage: spectate [free|follow|orbit|next]"),
+        }
+    }
+    //picking a mode or a target from the console also starts spectating
+    let start = !spectator.active && (toggle || mode.is_some() || next);
+    let stop = spectator.active && toggle;
+
+    let Ok((player_transform, player_camera, mut player_render)) = player_query.get_single_mut() else {
+        return;
+    };
+    let Ok((mut spectator_transform, mut spectator_render)) = spectator_query.get_single_mut() else {
+        return;
+    };
+    if start {
+        //picks up exactly where the player was looking
+        *spectator_transform = *player_transform;
+        spectator.yaw = player_camera.rotation.y;
+        spectator.pitch = player_camera.rotation.x;
+        spectator.active = true;
+        console.print("spectating");
+    } else if stop {
+        spectator.active = false;
+        console.print("back to the player");
+    }
+    player_render.is_active = !spectator.active;
+    spectator_render.is_active = spectator.active;
+    if !spectator.active {
+        return;
+    }
+
+    if let Some(mode) = mode {
+        spectator.mode = mode;
+        console.print(format!("spectator mode {}", mode.label()));
+    }
+    if next {
+        let targets = spectator_targets(&enemy_query);
+        spectator.target = cycle_target(spectator.target, &targets, 1);
+        if spectator.target.is_none() {
+            console.print("no enemies to spectate");
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_spectator(
+    time: Res<Time>,
+    cursor_lock_state: Res<CursorLockState>,
+    sensitivity: Res<SensitivitySettings>,
+    spectator_input: Res<SpectatorInput>,
+    mut spectator: ResMut<Spectator>,
+    mut motion_evr: EventReader<MouseMotion>,
+    mut wheel_events: EventReader<MouseWheel>,
+    enemy_query: Query<(Entity, &Transform, &Enemy), Without<SpectatorCamera>>,
+    mut spectator_query: Query<&mut Transform, With<SpectatorCamera>>,
+    mut text_query: Query<&mut Text, With<SpectatorText>>,
+) {
+    if !spectator.active {
+        motion_evr.clear();
+        wheel_events.clear();
+        for mut text in text_query.iter_mut() {
+            text.sections[0].value.clear();
+        }
+        return;
+    }
+    let input = &spectator_input.0;
+    let delta = time.delta_seconds();
+
+    if cursor_lock_state.state {
+        let turn = motion_evr.iter().map(|ev| ev.delta).sum::<Vec2>() * sensitivity.radians_per_count();
+        spectator.yaw -= turn.x;
+        spectator.pitch -= turn.y;
+    } else {
+        motion_evr.clear();
+    }
+    spectator.yaw -= input.look.x * spectator.stick_speed * delta;
+    spectator.pitch += input.look.y * spectator.stick_speed * delta;
+    spectator.pitch = spectator.pitch.clamp(-88f32.to_radians(), 88f32.to_radians());
+
+    for ev in wheel_events.iter() {
+        let factor = if ev.y > 0. { 1.25 } else if ev.y < 0. { 0.8 } else { 1. };
+        if spectator.mode == SpectatorMode::Orbit {
+            //scrolling up pulls the camera in
+            spectator.orbit_distance = (spectator.orbit_distance / factor)
+                .clamp(spectator.min_orbit_distance, spectator.max_orbit_distance);
+        } else {
+            spectator.speed = (spectator.speed * factor).clamp(spectator.min_speed, spectator.max_speed);
+        }
+    }
+
+    if input.just_pressed(Action::Reload) {
+        spectator.mode = spectator.mode.next();
+    }
+    let targets = spectator_targets(&enemy_query);
+    if input.just_pressed(Action::Fire) {
+        spectator.target = cycle_target(spectator.target, &targets, 1);
+    }
+    if input.just_pressed(Action::Aim) {
+        spectator.target = cycle_target(spectator.target, &targets, -1);
+    }
+    //a target that died or despawned hands over to the next one
+    if spectator.mode != SpectatorMode::Free && !spectator.target.is_some_and(|target| targets.contains(&target)) {
+        spectator.target = cycle_target(None, &targets, 1);
+    }
+    let target = spectator
+        .target
+        .and_then(|target| enemy_query.get(target).ok())
+        .map(|(_, transform, _)| transform);
+
+    let Ok(mut transform) = spectator_query.get_single_mut() else {
+        return;
+    };
+    let look_rotation = Quat::from_axis_angle(Vec3::Y, spectator.yaw) * Quat::from_axis_angle(Vec3::X, spectator.pitch);
+    match (spectator.mode, target) {
+        (SpectatorMode::Follow, Some(target)) => {
+            let head = target.translation + Vec3::Y * EYE_HEIGHT;
+            let forward = (target.rotation * MODEL_FORWARD * Vec3::new(1., 0., 1.)).normalize_or_zero();
+            transform.translation = head - forward * spectator.follow_distance + Vec3::Y * spectator.follow_height;
+            transform.look_at(head + forward * spectator.follow_distance, Vec3::Y);
+        }
+        (SpectatorMode::Orbit, Some(target)) => {
+            let head = target.translation + Vec3::Y * EYE_HEIGHT;
+            transform.translation = head - look_rotation * Vec3::NEG_Z * spectator.orbit_distance;
+            transform.rotation = look_rotation;
+        }
+        //free mode, or nobody left to watch
+        _ => {
+            let wish = (look_rotation * Vec3::NEG_Z * input.movement.y
+                + look_rotation * Vec3::X * input.movement.x
+                + Vec3::Y * input.axis(Action::Crouch, Action::Jump))
+            .clamp_length_max(1.);
+            let speed = if input.pressed(Action::Sprint) {
+                spectator.speed * spectator.fast_multiplier
+            } else {
+                spectator.speed
+            };
+            transform.translation += wish * speed * delta;
+            transform.rotation = look_rotation;
+        }
+    }
+
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = match (spectator.mode, target) {
+            (SpectatorMode::Free, _) | (_, None) => format!("SPECTATING ({}) speed {:.0}", spectator.mode.label(), spectator.speed),
+            (mode, Some(_)) => format!(
+                "SPECTATING ({}) enemy {} of {}",
+                mode.label(),
+                targets.iter().position(|entity| Some(*entity) == spectator.target).map_or(0, |index| index + 1),
+                targets.len()
+            ),
+        };
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::Velocity;
+
+use crate::{
+    crouch::Crouch,
+    fps_camera::FPSCamera,
+    gun_control::GunController,
+    input_map::{Action, ActionState},
+    player_health::PlayerHealth,
+};
+
+#[derive(Component)]
+pub struct StaminaBar;
+
+#[derive(Component)]
+pub struct Sprint {
+    pub sprinting: bool,
+    pub stamina: f32,
+    pub max_stamina: f32,
+    //per second while sprinting
+    pub drain_rate: f32,
+    //per second once the regen delay has passed
+    pub regen_rate: f32,
+    //seconds after sprinting stops before stamina comes back
+    pub regen_delay: f32,
+    pub regen_timer: f32,
+    //once drained, stamina has to climb back to this before sprinting is allowed again
+    pub min_stamina_to_start: f32,
+    //applied to FPSMovement::speed while sprinting
+    pub speed_multiplier: f32,
+}
+
+impl Sprint {
+    pub fn new(max_stamina: f32, speed_multiplier: f32) -> Self {
+        Sprint {
+            sprinting: false,
+            stamina: max_stamina,
+            max_stamina,
+            drain_rate: 20.,
+            regen_rate: 15.,
+            regen_delay: 1.,
+            regen_timer: 0.,
+            min_stamina_to_start: max_stamina * 0.2,
+            speed_multiplier,
+        }
+    }
+
+    pub fn speed_scale(&self) -> f32 {
+        if self.sprinting {
+            self.speed_multiplier
+        } else {
+            1.
+        }
+    }
+}
+
+//sprinting only happens moving forwards, standing up and with stamina left
+pub fn update_sprint(
+    fixed_time: Res<FixedTime>,
+    action_state: Res<ActionState>,
+    mut player_query: Query<(&mut Sprint, &Velocity, &PlayerHealth, Option<&Crouch>), With<FPSCamera>>,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    for (mut sprint, velocity, player_health, crouch) in player_query.iter_mut() {
+        let moving = Vec2::new(velocity.linvel.x, velocity.linvel.z).length() > 0.5;
+        let crouched = crouch.is_some_and(|crouch| crouch.wants_crouch || crouch.amount > 0.);
+        let can_sprint = !player_health.dead
+            && !crouched
+            && action_state.pressed(Action::Sprint)
+            && action_state.movement.y > 0.5
+            && moving;
+
+        if sprint.sprinting {
+            sprint.sprinting = can_sprint && sprint.stamina > 0.;
+        } else {
+            sprint.sprinting = can_sprint && sprint.stamina >= sprint.min_stamina_to_start;
+        }
+
+        if sprint.sprinting {
+            sprint.stamina = (sprint.stamina - sprint.drain_rate * delta).max(0.);
+            sprint.regen_timer = sprint.regen_delay;
+        } else if sprint.regen_timer > 0. {
+            sprint.regen_timer -= delta;
+        } else {
+            sprint.stamina = (sprint.stamina + sprint.regen_rate * delta).min(sprint.max_stamina);
+        }
+    }
+}
+
+//keeps the gun lowered while sprinting and for the weapon's sprint-to-fire time afterwards
+pub fn apply_sprint_to_gun(
+    mut gun_query: Query<&mut GunController>,
+    sprint_query: Query<&Sprint>,
+) {
+    let Ok(sprint) = sprint_query.get_single() else {
+        return;
+    };
+    if !sprint.sprinting {
+        return;
+    }
+    for mut gun_controller in gun_query.iter_mut() {
+        gun_controller.sprint_ready_timer = gun_controller.sprint_to_fire_time;
+    }
+}
+
+pub fn update_stamina_bar(
+    sprint_query: Query<&Sprint>,
+    mut bar_query: Query<&mut Style, With<StaminaBar>>,
+) {
+    let Ok(sprint) = sprint_query.get_single() else {
+        return;
+    };
+    for mut style in bar_query.iter_mut() {
+        style.size.width = Val::Percent(sprint.stamina / sprint.max_stamina * 100.);
+    }
+}
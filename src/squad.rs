@@ -0,0 +1,146 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    enemy::Enemy,
+    enemy_ai::EnemyStimuli,
+    enemy_perception::Perception,
+    ragdoll::EnemyKilledEvent,
+    team::Team,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SquadRole {
+    //keeps the player pinned with faster, less accurate fire
+    Suppress,
+    //circles round to the player's side instead of walking straight at them
+    Flank,
+    //stays put and only fights what comes into range
+    Hold,
+}
+
+impl SquadRole {
+    //the first member suppresses, the rest alternate between flanking and holding
+    pub fn for_index(index: usize) -> Self {
+        match index {
+            0 => SquadRole::Suppress,
+            i if i % 2 == 1 => SquadRole::Flank,
+            _ => SquadRole::Hold,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SquadMember {
+    pub squad: u32,
+    pub role: SquadRole,
+}
+
+#[derive(Default)]
+pub struct SquadState {
+    //time until the next member is allowed to fire
+    pub fire_cooldown: f32,
+}
+
+#[derive(Resource)]
+pub struct Squads {
+    pub states: HashMap<u32, SquadState>,
+    //minimum seconds between two members of a squad firing
+    pub fire_stagger: f32,
+    //enemies outside the squad this close to a killed member also come to help
+    pub help_radius: f32,
+}
+
+impl Default for Squads {
+    fn default() -> Self {
+        Squads {
+            states: HashMap::default(),
+            fire_stagger: 0.35,
+            help_radius: 20.,
+        }
+    }
+}
+
+impl Squads {
+    pub fn can_fire(&self, squad: u32) -> bool {
+        self.states.get(&squad).is_none_or(|state| state.fire_cooldown <= 0.)
+    }
+
+    pub fn fired(&mut self, squad: u32) {
+        let fire_stagger = self.fire_stagger;
+        self.states.entry(squad).or_default().fire_cooldown = fire_stagger;
+    }
+}
+
+//hands roles out to the living members and lets everyone in a squad know what its best informed member knows
+pub fn update_squads(
+    fixed_time: Res<FixedTime>,
+    mut squads: ResMut<Squads>,
+    mut member_query: Query<(Entity, &Enemy, &mut SquadMember, &mut Perception, &mut EnemyStimuli)>,
+) {
+    for state in squads.states.values_mut() {
+        state.fire_cooldown -= fixed_time.period.as_secs_f32();
+    }
+
+    let mut members: HashMap<u32, Vec<Entity>> = HashMap::default();
+    for (entity, enemy, squad_member, _, _) in member_query.iter() {
+        if enemy.health > 0. {
+            members.entry(squad_member.squad).or_default().push(entity);
+        }
+    }
+    squads.states.retain(|squad, _| members.contains_key(squad));
+
+    for (squad, mut entities) in members {
+        squads.states.entry(squad).or_default();
+        //sorted so roles don't shuffle around between frames
+        entities.sort();
+
+        let freshest = entities
+            .iter()
+            .filter_map(|entity| member_query.get(*entity).ok())
+            .filter_map(|(_, _, _, perception, _)| perception.memory)
+            .min_by(|a, b| a.age.total_cmp(&b.age));
+
+        for (index, entity) in entities.iter().enumerate() {
+            let Ok((_, _, mut squad_member, mut perception, mut stimuli)) = member_query.get_mut(*entity) else {
+                continue;
+            };
+            squad_member.role = SquadRole::for_index(index);
+            let Some(freshest) = freshest else {
+                continue;
+            };
+            if perception.memory.is_none_or(|memory| memory.age > freshest.age) {
+                perception.memory = Some(freshest);
+                stimuli.target_position = Some(freshest.position);
+                stimuli.certainty = perception.certainty();
+            }
+        }
+    }
+}
+
+//a killed member points its squad and anyone close by at whoever killed it, unless that was one of their own
+#[allow(clippy::type_complexity)]
+pub fn call_for_help(
+    squads: Res<Squads>,
+    mut killed_events: EventReader<EnemyKilledEvent>,
+    mut enemy_query: Query<(&Enemy, &Transform, &Team, Option<&SquadMember>, &mut Perception, &mut EnemyStimuli)>,
+) {
+    for event in killed_events.iter() {
+        let Ok((_, dead_transform, _, dead_member, _, _)) = enemy_query.get(event.enemy) else {
+            continue;
+        };
+        let dead_position = dead_transform.translation;
+        let dead_squad = dead_member.map(|member| member.squad);
+
+        for (enemy, transform, team, squad_member, mut perception, mut stimuli) in enemy_query.iter_mut() {
+            if enemy.health <= 0. || !event.team.is_hostile_to(*team) {
+                continue;
+            }
+            let same_squad = dead_squad.is_some() && squad_member.map(|member| member.squad) == dead_squad;
+            if same_squad || transform.translation.distance(dead_position) <= squads.help_radius {
+                perception.remember(event.attacker_position);
+                stimuli.target_position = Some(event.attacker_position);
+                stimuli.certainty = 1.;
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    console::{Console, ConsoleCommand},
+    input_map::{Action, ActionState},
+};
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub struct Team(pub u8);
+
+impl Team {
+    pub const PLAYERS: Team = Team(0);
+    pub const ENEMIES: Team = Team(1);
+
+    pub fn is_hostile_to(&self, other: Team) -> bool {
+        *self != other
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FriendlyFireMode {
+    Off,
+    Reduced,
+    Full,
+}
+
+impl FriendlyFireMode {
+    pub fn next(&self) -> FriendlyFireMode {
+        match self {
+            FriendlyFireMode::Off => FriendlyFireMode::Reduced,
+            FriendlyFireMode::Reduced => FriendlyFireMode::Full,
+            FriendlyFireMode::Full => FriendlyFireMode::Off,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FriendlyFireMode::Off => "off",
+            FriendlyFireMode::Reduced => "reduced",
+            FriendlyFireMode::Full => "full",
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct FriendlyFire {
+    pub mode: FriendlyFireMode,
+    //fraction of damage teammates take from each other in reduced mode
+    pub reduced_scale: f32,
+}
+
+impl Default for FriendlyFire {
+    fn default() -> Self {
+        FriendlyFire {
+            mode: FriendlyFireMode::Off,
+            reduced_scale: 0.35,
+        }
+    }
+}
+
+impl FriendlyFire {
+    //what a hit from the attacker's team is multiplied by when it lands on the victim's team
+    pub fn damage_scale(&self, attacker: Team, victim: Team) -> f32 {
+        if attacker.is_hostile_to(victim) {
+            return 1.;
+        }
+        match self.mode {
+            FriendlyFireMode::Off => 0.,
+            FriendlyFireMode::Reduced => self.reduced_scale,
+            FriendlyFireMode::Full => 1.,
+        }
+    }
+}
+
+//cycles friendly fire through off, reduced and full, or sets it and the reduced scale from the console
+pub fn toggle_friendly_fire(
+    action_state: Res<ActionState>,
+    mut command_events: EventReader<ConsoleCommand>,
+    mut console: ResMut<Console>,
+    mut friendly_fire: ResMut<FriendlyFire>,
+) {
+    let mut mode = action_state.just_pressed(Action::FriendlyFire).then_some(friendly_fire.mode.next());
+    for command in command_events.iter() {
+        if command.name != "friendly_fire" {
+            continue;
+        }
+        match command.args.first().map(String::as_str) {
+            Some("off") => mode = Some(FriendlyFireMode::Off),
+            Some("reduced") => mode = Some(FriendlyFireMode::Reduced),
+            Some("full") => mode = Some(FriendlyFireMode::Full),
+            _ => {
+                console.print("usage: friendly_fire off|reduced|full [scale]");
+                continue;
+            }
+        }
+        if let Some(scale) = command.args.get(1).and_then(|arg| arg.parse::<f32>().ok()) {
+            friendly_fire.reduced_scale = scale.clamp(0., 1.);
+        }
+    }
+    if let Some(mode) = mode {
+        friendly_fire.mode = mode;
+        console.print(format!("friendly fire {} ({} in reduced)", mode.label(), friendly_fire.reduced_scale));
+    }
+}
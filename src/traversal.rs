@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    character_controller::{MAX_STEP_HEIGHT, PLAYER_HALF_EXTENTS},
+    crouch::Crouch,
+    enemy::EnemyHitboxFilter,
+    fps_camera::FPSCamera,
+    fps_movement::FPSMovement,
+    input_map::{Action, ActionState},
+    noclip::Noclip,
+    player_health::PlayerHealth,
+};
+
+//a climbable box, the player climbs while their feet are inside it
+#[derive(Component)]
+pub struct Ladder {
+    pub half_extents: Vec3,
+}
+
+impl Ladder {
+    pub fn contains(&self, transform: &Transform, point: Vec3) -> bool {
+        let local = transform.rotation.inverse() * (point - transform.translation);
+        local.abs().cmple(self.half_extents).all()
+    }
+}
+
+//the lowest ledge that counts, anything below it is just stepped up
+const MIN_LEDGE_HEIGHT: f32 = MAX_STEP_HEIGHT + 0.1;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum TraversalState {
+    Free,
+    //following a path up onto or over an obstacle
+    Mantling {
+        path: Vec<Vec3>,
+        travelled: f32,
+        speed: f32,
+        timer: f32,
+    },
+    Climbing {
+        ladder: Entity,
+    },
+}
+
+#[derive(Component)]
+pub struct Traversal {
+    pub state: TraversalState,
+    //how far ahead a ledge can be and still be grabbed
+    pub reach: f32,
+    //highest ledge above the feet that can be mantled, roughly chest height
+    pub max_mantle_height: f32,
+    //cover up to this high is vaulted over when there is a drop behind it
+    pub max_vault_height: f32,
+    //how far past the front face of cover to look for the other side
+    pub vault_depth: f32,
+    pub mantle_speed: f32,
+    pub vault_speed: f32,
+    pub climb_speed: f32,
+    //seconds after jumping off a ladder before it can be grabbed again
+    pub ladder_cooldown: f32,
+    pub ladder_timer: f32,
+    //gravity scale to put back once the player is free again
+    pub gravity_scale: f32,
+}
+
+impl Default for Traversal {
+    fn default() -> Self {
+        Traversal {
+            state: TraversalState::Free,
+            reach: 0.8,
+            max_mantle_height: 2.2,
+            max_vault_height: 1.4,
+            vault_depth: 1.2,
+            mantle_speed: 4.,
+            vault_speed: 5.,
+            climb_speed: 2.,
+            ladder_cooldown: 0.5,
+            ladder_timer: 0.,
+            gravity_scale: 1.,
+        }
+    }
+}
+
+impl Traversal {
+    pub fn is_free(&self) -> bool {
+        self.state == TraversalState::Free
+    }
+
+    pub fn is_climbing(&self) -> bool {
+        matches!(self.state, TraversalState::Climbing { .. })
+    }
+}
+
+fn path_length(path: &[Vec3]) -> f32 {
+    path.windows(2).map(|pair| pair[0].distance(pair[1])).sum()
+}
+
+fn point_along(path: &[Vec3], mut distance: f32) -> Vec3 {
+    for pair in path.windows(2) {
+        let length = pair[0].distance(pair[1]);
+        if distance <= length && length > 0. {
+            return pair[0].lerp(pair[1], distance / length);
+        }
+        distance -= length;
+    }
+    path.last().copied().unwrap_or_default()
+}
+
+//casts the player-sized box straight down from high above the point and returns the height of the first surface
+fn surface_below(
+    rapier_context: &RapierContext,
+    filter: QueryFilter,
+    body: &Collider,
+    half_height: f32,
+    point: Vec3,
+    drop: f32,
+) -> Option<f32> {
+    let (_, toi) = rapier_context.cast_shape(point, Quat::IDENTITY, Vec3::NEG_Y, body, drop, filter)?;
+    //already overlapping means there's no room for the player up there
+    if toi.toi <= 0. {
+        return None;
+    }
+    Some(point.y - half_height - toi.toi)
+}
+
+//works out a path onto or over whatever is in front of the player, if anything can be mantled or vaulted
+#[allow(clippy::too_many_arguments)]
+fn find_ledge(
+    rapier_context: &RapierContext,
+    filter: QueryFilter,
+    traversal: &Traversal,
+    position: Vec3,
+    forward: Vec3,
+    half_height: f32,
+    half_width: f32,
+) -> Option<(Vec<Vec3>, f32)> {
+    let feet = position - Vec3::Y * half_height;
+
+    //something solid in front between step height and the highest ledge
+    let probe_half_height = (traversal.max_mantle_height - MIN_LEDGE_HEIGHT) / 2.;
+    let probe = Collider::cuboid(half_width * 0.9, probe_half_height, half_width * 0.9);
+    let probe_position = feet + Vec3::Y * (MIN_LEDGE_HEIGHT + probe_half_height);
+    let (_, wall) = rapier_context.cast_shape(probe_position, Quat::IDENTITY, forward, &probe, traversal.reach, filter)?;
+
+    let body = Collider::cuboid(half_width, half_height, half_width);
+    let above = half_height + traversal.max_mantle_height + 0.05;
+    let drop = traversal.max_mantle_height + 0.05;
+    //stand the player on the front of the ledge, just past its edge
+    let onto = feet + forward * (wall.toi + half_width * 2. + 0.05);
+    let top = surface_below(rapier_context, filter, &body, half_height, onto + Vec3::Y * above, drop)?;
+    let height = top - feet.y;
+    if height < MIN_LEDGE_HEIGHT || height > traversal.max_mantle_height {
+        return None;
+    }
+
+    //room to rise straight up to the ledge before moving across it
+    let rise = top + half_height + 0.05 - position.y;
+    if rapier_context.cast_shape(position, Quat::IDENTITY, Vec3::Y, &body, rise, filter).is_some() {
+        return None;
+    }
+    let lifted = Vec3::new(position.x, top + half_height + 0.05, position.z);
+
+    //low cover with a drop behind it is vaulted instead of climbed on to
+    if height <= traversal.max_vault_height {
+        let beyond = feet + forward * (wall.toi + traversal.vault_depth + half_width * 2.);
+        let floor = surface_below(
+            rapier_context,
+            filter,
+            &body,
+            half_height,
+            Vec3::new(beyond.x, lifted.y, beyond.z),
+            height + half_height * 2.,
+        );
+        if let Some(floor) = floor {
+            if floor < top - 0.3 {
+                let landing = Vec3::new(beyond.x, floor + half_height + 0.05, beyond.z);
+                let over = Vec3::new(onto.x, lifted.y, onto.z);
+                return Some((vec![position, lifted, over, landing], traversal.vault_speed));
+            }
+        }
+    }
+    let standing = Vec3::new(onto.x, lifted.y, onto.z);
+    Some((vec![position, lifted, standing], traversal.mantle_speed))
+}
+
+//starts and drives mantles and vaults, and switches the player onto and off ladders
+#[allow(clippy::type_complexity)]
+pub fn update_traversal(
+    fixed_time: Res<FixedTime>,
+    action_state: Res<ActionState>,
+    rapier_context: Res<RapierContext>,
+    hitbox_query: Query<(), EnemyHitboxFilter>,
+    ladder_query: Query<(Entity, &Transform, &Ladder), Without<FPSMovement>>,
+    mut player_query: Query<
+        (
+            &Transform,
+            &mut Traversal,
+            &mut Velocity,
+            &mut GravityScale,
+            &FPSCamera,
+            &PlayerHealth,
+            Option<&Crouch>,
+        ),
+        Without<Noclip>,
+    >,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    let not_hitbox = |collider: Entity| !hitbox_query.contains(collider);
+    let filter = QueryFilter::only_fixed().exclude_sensors().predicate(&not_hitbox);
+    for (transform, mut traversal, mut velocity, mut gravity_scale, camera, player_health, crouch) in
+        player_query.iter_mut()
+    {
+        let half_height = crouch.map_or(PLAYER_HALF_EXTENTS.y, |crouch| crouch.half_height());
+        let half_width = crouch.map_or(PLAYER_HALF_EXTENTS.x, |crouch| crouch.half_width);
+        let forward = Vec3::new(-f32::sin(camera.rotation.y), 0., -f32::cos(camera.rotation.y));
+        //ladders go by the feet so the player climbs until they are stood level with the top
+        let feet = transform.translation - Vec3::Y * (half_height - 0.1);
+        traversal.ladder_timer -= delta;
+
+        if player_health.dead {
+            if !traversal.is_free() {
+                traversal.state = TraversalState::Free;
+                gravity_scale.0 = traversal.gravity_scale;
+            }
+            continue;
+        }
+
+        match traversal.state.clone() {
+            TraversalState::Free => {
+                let ladder = ladder_query
+                    .iter()
+                    .find(|(_, ladder_transform, ladder)| ladder.contains(ladder_transform, feet))
+                    .map(|(entity, ..)| entity);
+                if let Some(ladder) = ladder {
+                    if traversal.ladder_timer <= 0. {
+                        traversal.gravity_scale = gravity_scale.0;
+                        gravity_scale.0 = 0.;
+                        velocity.linvel = Vec3::ZERO;
+                        traversal.state = TraversalState::Climbing { ladder };
+                        continue;
+                    }
+                }
+
+                //a jump into a ledge while pushing towards it grabs it, in the air or on the ground
+                if !action_state.pressed_this_tick(Action::Jump) || action_state.movement.y <= 0.5 {
+                    continue;
+                }
+                let Some((path, speed)) =
+                    find_ledge(&rapier_context, filter, &traversal, transform.translation, forward, half_height, half_width)
+                else {
+                    continue;
+                };
+                traversal.gravity_scale = gravity_scale.0;
+                gravity_scale.0 = 0.;
+                let timer = path_length(&path) / speed * 1.5;
+                traversal.state = TraversalState::Mantling {
+                    path,
+                    travelled: 0.,
+                    speed,
+                    timer,
+                };
+            }
+            TraversalState::Mantling { path, travelled, speed, timer } => {
+                let travelled = travelled + speed * delta;
+                let timer = timer - delta;
+                let length = path_length(&path);
+                let target = point_along(&path, travelled);
+                //give up if something got in the way
+                if timer <= 0. || (travelled >= length && transform.translation.distance(target) < 0.1) {
+                    traversal.state = TraversalState::Free;
+                    gravity_scale.0 = traversal.gravity_scale;
+                    velocity.linvel = forward * speed * 0.3;
+                    continue;
+                }
+                //driven through the velocity so physics still stops the player going through anything
+                velocity.linvel = (target - transform.translation) / delta.max(0.001);
+                traversal.state = TraversalState::Mantling { path, travelled, speed, timer };
+            }
+            TraversalState::Climbing { ladder } => {
+                let still_on = ladder_query
+                    .get(ladder)
+                    .is_ok_and(|(_, ladder_transform, ladder)| ladder.contains(ladder_transform, feet));
+                let jump_off = action_state.pressed_this_tick(Action::Jump);
+                if still_on && !jump_off {
+                    continue;
+                }
+                traversal.state = TraversalState::Free;
+                gravity_scale.0 = traversal.gravity_scale;
+                if jump_off {
+                    traversal.ladder_timer = traversal.ladder_cooldown;
+                    velocity.linvel = -forward * 2. + Vec3::Y * 3.;
+                } else if velocity.linvel.y > 0. {
+                    //climbing out of the top carries the player over onto the floor
+                    velocity.linvel = forward * traversal.climb_speed + Vec3::Y * 2.;
+                }
+            }
+        }
+    }
+}